@@ -0,0 +1,60 @@
+use std::hint::black_box;
+
+use bloom_filter::{
+    BlockedBloomFilter, BloomFilter, BloomParams, RegisterBlockedBloomFilter, SeededState,
+};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const NUM_BITS: usize = 100_000_000;
+const LOOKUPS: u64 = 10_000;
+
+fn lookups(c: &mut Criterion) {
+    let params = BloomParams::from_bits_and_fp_rate(NUM_BITS, 0.01).unwrap();
+    let items = params.items_count() as u64;
+
+    let mut bloom = BloomFilter::with_params(params, SeededState::with_seed(0));
+    let mut blocked = BlockedBloomFilter::with_params(params, SeededState::with_seed(0));
+    let mut register_blocked =
+        RegisterBlockedBloomFilter::with_params(params, SeededState::with_seed(0));
+    for i in 0..items {
+        bloom.insert(&i);
+        blocked.insert(&i);
+        register_blocked.insert(&i);
+    }
+
+    // half of the lookups hit an inserted item, spread over the whole filter
+    let keys: Vec<u64> = (0..LOOKUPS)
+        .map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15) % (2 * items))
+        .collect();
+
+    let mut group = c.benchmark_group("contains_100M_bits");
+    group.throughput(Throughput::Elements(LOOKUPS));
+    group.bench_function("bloom", |b| {
+        b.iter(|| {
+            keys.iter()
+                .filter(|key| bloom.contains(black_box(key)))
+                .count()
+        })
+    });
+    group.bench_function("bloom_batch", |b| {
+        b.iter(|| bloom.contains_batch(black_box(&keys)).count_ones())
+    });
+    group.bench_function("blocked", |b| {
+        b.iter(|| {
+            keys.iter()
+                .filter(|key| blocked.contains(black_box(key)))
+                .count()
+        })
+    });
+    group.bench_function("register_blocked", |b| {
+        b.iter(|| {
+            keys.iter()
+                .filter(|key| register_blocked.contains(black_box(key)))
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, lookups);
+criterion_main!(benches);
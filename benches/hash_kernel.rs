@@ -0,0 +1,56 @@
+use std::hash::{BuildHasher, Hash, Hasher};
+use std::hint::black_box;
+
+use bloom_filter::{BloomFilter, SeededState};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+const KEY_SIZE: usize = 1024;
+
+fn keys() -> Vec<String> {
+    (0..1000)
+        .map(|i| format!("{i:0>width$}", width = KEY_SIZE))
+        .collect()
+}
+
+/// Reference two pass kernel, which feeds the item to two independent hashers.
+fn two_pass(state: &SeededState, item: &str) -> (u64, u64) {
+    let mut hasher2 = state.build_hasher();
+    hasher2.write_u8(1);
+    item.hash(&mut hasher2);
+    (state.hash_one(item), hasher2.finish())
+}
+
+fn bloom_1kib_keys(c: &mut Criterion) {
+    let keys = keys();
+    let mut bloom = BloomFilter::with_seed(keys.len(), 0.01, 0);
+    let state = SeededState::with_seed(0);
+
+    let mut group = c.benchmark_group("bloom_1KiB");
+    group.throughput(Throughput::Bytes((keys.len() * KEY_SIZE) as u64));
+    // hashing alone with the two pass kernel costs more than a whole insert or lookup with the crate kernel
+    group.bench_function("two_pass_hashing", |b| {
+        b.iter(|| {
+            for key in &keys {
+                black_box(two_pass(&state, black_box(key)));
+            }
+        })
+    });
+    group.bench_function("insert", |b| {
+        b.iter(|| {
+            for key in &keys {
+                bloom.insert(black_box(key));
+            }
+        })
+    });
+    group.bench_function("contains", |b| {
+        b.iter(|| {
+            for key in &keys {
+                black_box(bloom.contains(black_box(key)));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bloom_1kib_keys);
+criterion_main!(benches);
@@ -0,0 +1,124 @@
+use std::hint::black_box;
+
+use bloom_filter::{
+    BinaryFuseFilter16, BinaryFuseFilter8, BloomFilter, RibbonFilter, RibbonVariant, SeededState,
+    XorFilter16, XorFilter8,
+};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+const KEYS: u64 = 1_000_000;
+const LOOKUPS: u64 = 10_000;
+
+fn construction(c: &mut Criterion) {
+    let keys: Vec<u64> = (0..KEYS).collect();
+
+    let mut group = c.benchmark_group("build_1M_keys");
+    group.throughput(Throughput::Elements(KEYS));
+    group.sample_size(10);
+    group.bench_function("bloom_8", |b| {
+        b.iter_batched(
+            || BloomFilter::<u64>::with_seed(KEYS as usize, 1.0 / 256.0, 0),
+            |mut bloom| {
+                bloom.extend(&keys);
+                bloom
+            },
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("xor_8", |b| {
+        b.iter(|| XorFilter8::<u64>::with_seed(black_box(&keys), 0))
+    });
+    group.bench_function("binary_fuse_8", |b| {
+        b.iter(|| BinaryFuseFilter8::<u64>::with_seed(black_box(&keys), 0))
+    });
+    group.bench_function("ribbon_8", |b| b.iter(|| ribbon(black_box(&keys), 8)));
+    group.finish();
+}
+
+fn ribbon(keys: &[u64], result_bits: u32) -> RibbonFilter<u64> {
+    RibbonFilter::try_with_options(
+        keys,
+        result_bits,
+        128,
+        RibbonVariant::Standard,
+        SeededState::with_seed(0),
+    )
+    .unwrap()
+}
+
+fn lookups(c: &mut Criterion) {
+    let keys: Vec<u64> = (0..KEYS).collect();
+    // half of the lookups hit a key, spread over the whole filter
+    let lookups: Vec<u64> = (0..LOOKUPS)
+        .map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15) % (2 * KEYS))
+        .collect();
+
+    // the fingerprints of 8 and 16 bits give false positive rates of 1/256 and 1/65536
+    for (bits, fp_rate) in [(8, 1.0 / 256.0), (16, 1.0 / 65536.0)] {
+        let mut bloom = BloomFilter::with_seed(KEYS as usize, fp_rate, 0);
+        bloom.extend(&keys);
+
+        let mut group = c.benchmark_group(format!("contains_1M_keys_{bits}_bits"));
+        group.throughput(Throughput::Elements(LOOKUPS));
+        group.bench_function("bloom", |b| {
+            b.iter(|| {
+                lookups
+                    .iter()
+                    .filter(|key| bloom.contains(black_box(key)))
+                    .count()
+            })
+        });
+        let ribbon = ribbon(&keys, bits);
+        group.bench_function("ribbon", |b| {
+            b.iter(|| {
+                lookups
+                    .iter()
+                    .filter(|key| ribbon.contains(black_box(key)))
+                    .count()
+            })
+        });
+        if bits == 8 {
+            let xor = XorFilter8::<u64>::with_seed(&keys, 0);
+            let fuse = BinaryFuseFilter8::<u64>::with_seed(&keys, 0);
+            group.bench_function("xor", |b| {
+                b.iter(|| {
+                    lookups
+                        .iter()
+                        .filter(|key| xor.contains(black_box(key)))
+                        .count()
+                })
+            });
+            group.bench_function("binary_fuse", |b| {
+                b.iter(|| {
+                    lookups
+                        .iter()
+                        .filter(|key| fuse.contains(black_box(key)))
+                        .count()
+                })
+            });
+        } else {
+            let xor = XorFilter16::<u64>::with_seed(&keys, 0);
+            let fuse = BinaryFuseFilter16::<u64>::with_seed(&keys, 0);
+            group.bench_function("xor", |b| {
+                b.iter(|| {
+                    lookups
+                        .iter()
+                        .filter(|key| xor.contains(black_box(key)))
+                        .count()
+                })
+            });
+            group.bench_function("binary_fuse", |b| {
+                b.iter(|| {
+                    lookups
+                        .iter()
+                        .filter(|key| fuse.contains(black_box(key)))
+                        .count()
+                })
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, construction, lookups);
+criterion_main!(benches);
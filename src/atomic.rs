@@ -0,0 +1,313 @@
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use bitvec::prelude::*;
+
+use crate::{hash, params, BloomError, BloomFilter, BloomParams, SeededState};
+
+/// A bloom filter which can be inserted into concurrently from several threads without locking.
+///
+/// The bitmap is stored as atomic 64-bit words and bits are set with a single `fetch_or`, so
+/// [`AtomicBloomFilter::insert`] only needs a shared reference: the filter can be shared behind an `Arc` and filled
+/// by any number of threads, without ever losing a bit. It uses the same hashing and bit layout as a
+/// [`BloomFilter`], and [`AtomicBloomFilter::to_bloom_filter`] returns the equivalent plain filter once
+/// insertions are over.
+///
+/// Memory accesses are relaxed: an item is reported as contained by any thread once its insertion returned, as long
+/// as the two threads synchronized in between (e.g. by joining a thread or through a channel). A lookup racing with
+/// the insertion of the same item may see it either way.
+///
+/// Example usage:
+/// ```
+/// use std::sync::Arc;
+/// use bloom_filter::AtomicBloomFilter;
+///
+/// let bloom = Arc::new(AtomicBloomFilter::new(1000, 0.01));
+/// let handles: Vec<_> = (0..4u32)
+///     .map(|thread| {
+///         let bloom = Arc::clone(&bloom);
+///         std::thread::spawn(move || {
+///             for i in 0..250u32 {
+///                 bloom.insert(&(thread * 250 + i));
+///             }
+///         })
+///     })
+///     .collect();
+/// for handle in handles {
+///     handle.join().unwrap();
+/// }
+/// assert!((0..1000u32).all(|i| bloom.contains(&i)));
+/// ```
+pub struct AtomicBloomFilter<T: ?Sized, S = SeededState> {
+    words: Vec<AtomicU64>,
+    optimal_m: u64,
+    optimal_k: u32,
+    capacity: usize,
+    inserted_count: AtomicU64,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized + Hash> AtomicBloomFilter<T> {
+    /// Create a new AtomicBloomFilter based on its size and the expected false positive rate, hashing items
+    /// with a random seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`AtomicBloomFilter::try_new`] for a fallible version.
+    pub fn new(items_count: usize, fp_rate: f64) -> Self {
+        params::expect_valid(Self::try_new(items_count, fp_rate), "bloom filter")
+    }
+
+    /// Fallible version of [`AtomicBloomFilter::new`], see [`BloomFilter::try_new`] for the possible errors.
+    pub fn try_new(items_count: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        let params = BloomParams::from_items_and_fp_rate(items_count, fp_rate)?;
+        Ok(Self::with_params(params, SeededState::new()))
+    }
+
+    /// Create a new AtomicBloomFilter hashing items deterministically from `seed`, see [`BloomFilter::with_seed`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`AtomicBloomFilter::try_with_seed`] for a fallible version.
+    pub fn with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Self {
+        params::expect_valid(
+            Self::try_with_seed(items_count, fp_rate, seed),
+            "bloom filter",
+        )
+    }
+
+    /// Fallible version of [`AtomicBloomFilter::with_seed`], see [`BloomFilter::try_new`]
+    /// for the possible errors.
+    pub fn try_with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Result<Self, BloomError> {
+        let params = BloomParams::from_items_and_fp_rate(items_count, fp_rate)?;
+        Ok(Self::with_params(params, SeededState::with_seed(seed)))
+    }
+}
+
+impl<T: ?Sized + Hash, S: BuildHasher + 'static> AtomicBloomFilter<T, S> {
+    /// Create a new AtomicBloomFilter sized by `params`, hashing items with hashers built by `hash_builder`.
+    pub fn with_params(params: BloomParams, hash_builder: S) -> Self {
+        AtomicBloomFilter {
+            words: (0..params.num_bits().div_ceil(64))
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            optimal_m: params.num_bits() as u64,
+            optimal_k: params.num_hashes(),
+            capacity: params.items_count(),
+            inserted_count: AtomicU64::new(0),
+            hash_builder,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Insert an element into the filter, possibly concurrently with other insertions and lookups.
+    pub fn insert(&self, item: &T) {
+        self.check_and_insert(item);
+    }
+
+    /// Checks if an element is contained in the filter, see [`BloomFilter::contains`].
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        (0..self.optimal_k as u64).all(|k_i| {
+            let (word, mask) = self.locate(h1, h2, k_i);
+            self.words[word].load(Ordering::Relaxed) & mask != 0
+        })
+    }
+
+    /// Insert an element into the filter, returning whether it was possibly already present.
+    ///
+    /// This returns `true` if all the bits of the item were already set before this call, with the same false
+    /// positive rate as [`AtomicBloomFilter::contains`]. Two threads racing to insert the same item may both
+    /// return `false`.
+    pub fn check_and_insert(&self, item: &T) -> bool {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        let mut present = true;
+        for k_i in 0..self.optimal_k as u64 {
+            let (word, mask) = self.locate(h1, h2, k_i);
+            present &= self.words[word].fetch_or(mask, Ordering::Relaxed) & mask != 0;
+        }
+        self.inserted_count.fetch_add(1, Ordering::Relaxed);
+        present
+    }
+
+    fn locate(&self, h1: u64, h2: u64, k_i: u64) -> (usize, u64) {
+        let index = hash::get_index(h1, h2, k_i, self.optimal_m);
+        (index / 64, 1 << (index % 64))
+    }
+}
+
+impl<T: ?Sized, S> AtomicBloomFilter<T, S> {
+    /// The number of bits of this filter.
+    pub fn num_bits(&self) -> usize {
+        self.optimal_m as usize
+    }
+
+    /// The number of hash functions of this filter.
+    pub fn num_hashes(&self) -> u32 {
+        self.optimal_k
+    }
+
+    /// The number of items this filter was sized for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of insertions performed on this filter, including repeated insertions of the same item.
+    pub fn inserted_count(&self) -> u64 {
+        self.inserted_count.load(Ordering::Relaxed)
+    }
+
+    /// The hash builder used by this filter.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Build the plain [`BloomFilter`] holding the same bits, hashing items the same way.
+    pub fn to_bloom_filter(&self) -> BloomFilter<T, S>
+    where
+        S: Clone,
+    {
+        let mut bitmap = BitVec::<u64, Lsb0>::from_vec(
+            self.words
+                .iter()
+                .map(|word| word.load(Ordering::Relaxed))
+                .collect(),
+        );
+        bitmap.truncate(self.optimal_m as usize);
+        BloomFilter {
+            bitmap,
+            optimal_m: self.optimal_m,
+            optimal_k: self.optimal_k,
+            capacity: self.capacity,
+            inserted_count: self.inserted_count(),
+            hash_builder: self.hash_builder.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread};
+
+    use super::*;
+
+    #[test]
+    fn insert() {
+        let bloom = AtomicBloomFilter::with_seed(100, 0.01, 0);
+        assert!(!bloom.contains("item"));
+        bloom.insert("item");
+        assert!(bloom.contains("item"));
+        assert!(!bloom.contains("other_item"));
+        assert_eq!(bloom.inserted_count(), 1);
+    }
+
+    #[test]
+    fn check_and_insert() {
+        let bloom = AtomicBloomFilter::with_seed(100, 0.01, 0);
+        assert!(!bloom.check_and_insert("item"));
+        assert!(bloom.check_and_insert("item"));
+        assert!(!bloom.check_and_insert("other_item"));
+        assert_eq!(bloom.inserted_count(), 3);
+    }
+
+    #[test]
+    fn same_bits_as_bloom_filter() {
+        let atomic = AtomicBloomFilter::with_seed(1000, 0.01, 3);
+        let mut bloom = BloomFilter::with_seed(1000, 0.01, 3);
+        for i in 0..1000u32 {
+            atomic.insert(&i);
+            bloom.insert(&i);
+        }
+        let converted = atomic.to_bloom_filter();
+        assert_eq!(converted.bitmap, bloom.bitmap);
+        assert_eq!(converted.inserted_count(), 1000);
+        assert!((0..1000u32).all(|i| converted.contains(&i)));
+    }
+
+    #[test]
+    fn concurrent_inserts_lose_no_bits() {
+        const THREADS: u32 = 16;
+        const PER_THREAD: u32 = 10_000;
+
+        // a small filter makes threads hit the same words as often as possible
+        let atomic = Arc::new(AtomicBloomFilter::with_seed(1000, 0.01, 0));
+        let mut bloom = BloomFilter::with_seed(1000, 0.01, 0);
+        for i in 0..THREADS * PER_THREAD {
+            bloom.insert(&i);
+        }
+
+        let handles: Vec<_> = (0..THREADS)
+            .map(|thread| {
+                let atomic = Arc::clone(&atomic);
+                thread::spawn(move || {
+                    for i in thread * PER_THREAD..(thread + 1) * PER_THREAD {
+                        atomic.insert(&i);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(atomic.to_bloom_filter().bitmap, bloom.bitmap);
+        assert_eq!(atomic.inserted_count(), (THREADS * PER_THREAD) as u64);
+    }
+
+    #[test]
+    fn concurrent_check_and_insert() {
+        const THREADS: u32 = 8;
+
+        // every thread inserts the same items, each of them must be reported absent by at least one thread
+        let atomic = Arc::new(AtomicBloomFilter::with_seed(100_000, 0.0001, 0));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let atomic = Arc::clone(&atomic);
+                thread::spawn(move || {
+                    (0..10_000u32)
+                        .map(|i| atomic.check_and_insert(&i))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let results: Vec<_> = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect();
+
+        let first_insertions = (0..10_000)
+            .filter(|&i| results.iter().any(|present| !present[i]))
+            .count();
+        // false positives of the first insertion may hide a few items
+        assert!(first_insertions > 9_990, "{first_insertions}");
+        assert!((0..10_000u32).all(|i| atomic.contains(&i)));
+    }
+
+    #[test]
+    fn is_send_and_sync() {
+        fn assert_send_sync<B: Send + Sync>() {}
+        assert_send_sync::<AtomicBloomFilter<str>>();
+        assert_send_sync::<AtomicBloomFilter<std::rc::Rc<u8>>>();
+    }
+
+    #[test]
+    fn try_with_seed_rejects_invalid_parameters() {
+        assert_eq!(
+            AtomicBloomFilter::<str>::try_with_seed(0, 0.01, 0).err(),
+            Some(BloomError::ZeroItemsCount)
+        );
+        assert!(matches!(
+            AtomicBloomFilter::<str>::try_with_seed(100, f64::NAN, 0),
+            Err(BloomError::InvalidFpRate(fp_rate)) if fp_rate.is_nan()
+        ));
+    }
+}
@@ -0,0 +1,256 @@
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+};
+
+use bitvec::prelude::*;
+
+use crate::{params, BloomFilter, BloomParams, SeededState};
+
+/// Number of items hashed ahead of probing the bitmap in batch operations.
+///
+/// This should be enough for the memory accesses of the batch to overlap, while keeping the prefetched words
+/// of the whole batch in the L1 cache.
+const BATCH_SIZE: usize = 16;
+
+impl<T: ?Sized + Hash> BloomFilter<T> {
+    /// Create a new BloomFilter sized for the items of `iter` at the expected false positive rate, hashing items
+    /// with a random seed, and insert them all.
+    ///
+    /// The filter is sized from the length of the iterator, so inserting more items later on raises its false
+    /// positive rate above `fp_rate`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fp_rate` is not in the open interval `(0, 1)` or if the filter would be too large to be allocated.
+    ///
+    /// Example usage:
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    ///
+    /// let items = ["a", "b", "c"];
+    /// let bloom = BloomFilter::<str>::from_iter_with_fp(items, 0.01);
+    /// assert!(bloom.contains_all(items));
+    /// ```
+    pub fn from_iter_with_fp<'a, I>(iter: I, fp_rate: f64) -> Self
+    where
+        T: 'a,
+        I: IntoIterator<Item = &'a T>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let iter = iter.into_iter();
+        // an empty filter still needs room for a single item
+        let mut bloom = params::expect_valid(
+            BloomParams::from_items_and_fp_rate(iter.len().max(1), fp_rate)
+                .map(|params| Self::with_params(params, SeededState::new())),
+            "bloom filter",
+        );
+        bloom.extend(iter);
+        bloom
+    }
+}
+
+impl<T: ?Sized + Hash, S: BuildHasher + 'static> BloomFilter<T, S> {
+    /// Checks if all the elements of `items` are contained in the filter, stopping at the first one which isn't.
+    pub fn contains_all<'a, Q, I>(&self, items: I) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + 'a,
+        I: IntoIterator<Item = &'a Q>,
+    {
+        let mut all = true;
+        self.contains_each(items, |contained| {
+            all = contained;
+            all
+        });
+        all
+    }
+
+    /// Checks if any of the elements of `items` is contained in the filter, stopping at the first one which is.
+    pub fn contains_any<'a, Q, I>(&self, items: I) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + 'a,
+        I: IntoIterator<Item = &'a Q>,
+    {
+        let mut any = false;
+        self.contains_each(items, |contained| {
+            any = contained;
+            !any
+        });
+        any
+    }
+
+    /// Checks which elements of `items` are contained in the filter.
+    ///
+    /// The `i`-th bit of the returned bitset is set if the `i`-th item is contained in the filter. Items are hashed
+    /// by batches, and the bitmap word holding the first bit of each item of a batch is prefetched before any of
+    /// them is probed. This overlaps the cache misses of lookups into filters larger than the CPU caches, most of
+    /// which only need that first bit to rule out an absent item.
+    ///
+    /// Example usage:
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    ///
+    /// let mut bloom = BloomFilter::new(100, 0.01);
+    /// bloom.extend(["a", "b"]);
+    /// let contained = bloom.contains_batch(["a", "b", "c"]);
+    /// assert_eq!(contained.iter_ones().collect::<Vec<_>>(), vec![0, 1]);
+    /// ```
+    pub fn contains_batch<'a, Q, I>(&self, items: I) -> BitVec<u64, Lsb0>
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + 'a,
+        I: IntoIterator<Item = &'a Q>,
+    {
+        // fill the words of the bitset by hand, which is much cheaper than pushing bits one by one
+        let mut words: Vec<u64> = Vec::new();
+        let mut len = 0;
+        self.contains_each(items, |contained| {
+            if len % 64 == 0 {
+                words.push(0);
+            }
+            if let Some(word) = words.last_mut() {
+                *word |= (contained as u64) << (len % 64);
+            }
+            len += 1;
+            true
+        });
+        let mut contained = BitVec::from_vec(words);
+        contained.truncate(len);
+        contained
+    }
+
+    /// Probe the filter for each element of `items` in order, until `f` returns `false`.
+    fn contains_each<'a, Q, I>(&self, items: I, mut f: impl FnMut(bool) -> bool)
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + 'a,
+        I: IntoIterator<Item = &'a Q>,
+    {
+        let mut items = items.into_iter();
+        let mut hashes = Vec::with_capacity(BATCH_SIZE);
+        while self.next_batch(&mut items, &mut hashes) {
+            for &(h1, h2, first_index) in &hashes {
+                // the first index was already computed to be prefetched, and rules out most absent items
+                let contained = self.bitmap[first_index]
+                    && (1..self.optimal_k as u64)
+                        .all(|k_i| self.bitmap[self.get_index(h1, h2, k_i)]);
+                if !f(contained) {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Hash the next batch of elements of `items` into `hashes` along with the index of their first bit, prefetching
+    /// the bitmap word holding it. Returns `false` once `items` is exhausted.
+    fn next_batch<'a, Q>(
+        &self,
+        items: &mut impl Iterator<Item = &'a Q>,
+        hashes: &mut Vec<(u64, u64, usize)>,
+    ) -> bool
+    where
+        T: Borrow<Q>,
+        Q: ?Sized + Hash + 'a,
+    {
+        hashes.clear();
+        let words = self.bitmap.as_raw_slice();
+        for item in items.take(BATCH_SIZE) {
+            let (h1, h2) = self.hash_kernel(item);
+            let first_index = self.get_index(h1, h2, 0);
+            prefetch(&words[first_index / 64]);
+            hashes.push((h1, h2, first_index));
+        }
+        !hashes.is_empty()
+    }
+}
+
+impl<'a, T: ?Sized + Hash + 'a, S: BuildHasher + 'static> Extend<&'a T> for BloomFilter<T, S> {
+    /// Insert all the elements of `iter`, hashing them by batches and prefetching bitmap words like
+    /// [`BloomFilter::contains_batch`].
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        let mut items = iter.into_iter();
+        let mut hashes = Vec::with_capacity(BATCH_SIZE);
+        while self.next_batch(&mut items, &mut hashes) {
+            for &(h1, h2, _) in &hashes {
+                self.insert_hashes(h1, h2);
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn prefetch(word: &u64) {
+    // SAFETY: prefetching is only a hint which never faults, and SSE is part of the x86_64 baseline
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>((word as *const u64).cast());
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = word;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extend() {
+        let mut bloom = BloomFilter::with_seed(1000, 0.01, 0);
+        let mut expected = BloomFilter::with_seed(1000, 0.01, 0);
+        let items: Vec<u32> = (0..1000).collect();
+        bloom.extend(&items);
+        for item in &items {
+            expected.insert(item);
+        }
+        assert_eq!(bloom.bitmap, expected.bitmap);
+        assert_eq!(bloom.inserted_count(), 1000);
+    }
+
+    #[test]
+    fn from_iter_with_fp() {
+        let items: Vec<u32> = (0..1000).collect();
+        let bloom = BloomFilter::from_iter_with_fp(&items, 0.01);
+        assert_eq!(bloom.capacity(), 1000);
+        assert_eq!(bloom.inserted_count(), 1000);
+        assert!(bloom.contains_all(&items));
+
+        let empty = BloomFilter::<u32>::from_iter_with_fp(&[], 0.01);
+        assert_eq!(empty.capacity(), 1);
+        assert_eq!(empty.inserted_count(), 0);
+    }
+
+    #[test]
+    fn contains_all_and_any() {
+        let mut bloom = BloomFilter::with_seed(1000, 0.01, 0);
+        bloom.extend(&(0..1000u32).collect::<Vec<_>>());
+        let present: Vec<u32> = (0..100).collect();
+        // skip the false positives of the filter
+        let absent: Vec<u32> = (100_000..)
+            .filter(|i| !bloom.contains(i))
+            .take(100)
+            .collect();
+        let mixed: Vec<u32> = absent.iter().chain(&present).copied().collect();
+
+        assert!(bloom.contains_all(&present));
+        assert!(!bloom.contains_all(&mixed));
+        assert!(bloom.contains_any(&mixed));
+        assert!(!bloom.contains_any(&absent));
+        assert!(bloom.contains_all(&[]));
+        assert!(!bloom.contains_any(&[]));
+    }
+
+    #[test]
+    fn contains_batch() {
+        let mut bloom = BloomFilter::with_seed(1000, 0.01, 0);
+        bloom.extend(&(0..1000u32).step_by(2).collect::<Vec<_>>());
+        let items: Vec<u32> = (0..1000).collect();
+        let contained = bloom.contains_batch(&items);
+        assert_eq!(contained.len(), items.len());
+        for (item, is_contained) in items.iter().zip(contained.iter()) {
+            assert_eq!(*is_contained, bloom.contains(item), "{item}");
+        }
+    }
+}
@@ -0,0 +1,424 @@
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+use crate::{hash, params, BloomError, BloomParams, SeededState};
+
+/// Number of 64-bit words in a block of a [`BlockedBloomFilter`], i.e. a 64-byte cache line.
+const BLOCK_WORDS: usize = 8;
+/// Number of bits in a block of a [`BlockedBloomFilter`].
+const BLOCK_BITS: usize = BLOCK_WORDS * u64::BITS as usize;
+
+/// A cache line worth of bits.
+#[derive(Clone, Copy, Default)]
+#[repr(align(64))]
+struct Block([u64; BLOCK_WORDS]);
+
+/// A bloom filter touching a single cache line per operation.
+///
+/// The bitmap is split into 512-bit blocks aligned on cache lines. `h1` selects the block of an item and all its
+/// `k` bits are derived from `h2` within that block, so an insertion or a lookup costs a single cache miss instead
+/// of up to `k` for a [`BloomFilter`](crate::BloomFilter). The price is a higher false positive rate for the same
+/// number of bits, since items are not spread evenly across blocks: [`BlockedBloomFilter::new`] accounts for it and
+/// needs about 4% more bits than a [`BloomFilter`](crate::BloomFilter) at a 1% false positive rate, 8% more at 0.1%
+/// and 15% more at 0.01%.
+///
+/// Example usage:
+/// ```
+/// use bloom_filter::BlockedBloomFilter;
+///
+/// let mut bloom = BlockedBloomFilter::new(100, 0.01);
+/// bloom.insert("item");
+/// assert!(bloom.contains("item"));
+/// ```
+pub struct BlockedBloomFilter<T: ?Sized, S = SeededState> {
+    blocks: Vec<Block>,
+    optimal_k: u32,
+    capacity: usize,
+    inserted_count: u64,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+/// A bloom filter touching a single 64-bit word per operation.
+///
+/// This is the register-blocked variant of [`BlockedBloomFilter`]: `h1` selects a 64-bit word and all the `k` bits
+/// of an item are set in that word with a single bitwise operation. This is the fastest filter of the crate, at
+/// the cost of a significantly higher false positive rate for the same number of bits, and of at most 64 hash
+/// functions: [`RegisterBlockedBloomFilter::new`] accounts for it and needs about 27% more bits than a
+/// [`BloomFilter`](crate::BloomFilter) at a 1% false positive rate, 67% more at 0.1% and 137% more at 0.01%.
+///
+/// Example usage:
+/// ```
+/// use bloom_filter::RegisterBlockedBloomFilter;
+///
+/// let mut bloom = RegisterBlockedBloomFilter::new(100, 0.01);
+/// bloom.insert("item");
+/// assert!(bloom.contains("item"));
+/// ```
+pub struct RegisterBlockedBloomFilter<T: ?Sized, S = SeededState> {
+    words: Vec<u64>,
+    optimal_k: u32,
+    capacity: usize,
+    inserted_count: u64,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+/// Map a hash uniformly onto `0..len` without a division.
+fn fast_range(hash: u64, len: usize) -> usize {
+    ((hash as u128 * len as u128) >> 64) as usize
+}
+
+/// The `k_i`-th bit of an item among a power of two number of bits, derived from `h2`.
+fn bit_in_block(h2: u64, k_i: u32, block_bits: usize) -> usize {
+    // an arithmetic progression start + k_i * step within the block only has block_bits² / 2 different patterns,
+    // which overlap a lot: mix h2 for each bit so that the bits of an item are independent, as assumed when
+    // sizing the filter
+    let hash = hash::mix(h2, (k_i as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    (hash >> (u64::BITS - block_bits.trailing_zeros())) as usize
+}
+
+/// Size a blocked filter with blocks of `block_bits` bits, see [`BloomParams::from_items_and_fp_rate_blocked`].
+fn blocked_params(
+    items_count: usize,
+    fp_rate: f64,
+    block_bits: usize,
+) -> Result<BloomParams, BloomError> {
+    BloomParams::from_items_and_fp_rate_blocked(items_count, fp_rate, block_bits, u64::BITS)
+}
+
+impl<T: ?Sized + Hash> BlockedBloomFilter<T> {
+    /// Create a new BlockedBloomFilter based on its size and the expected false positive rate, hashing items with
+    /// a random seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`BlockedBloomFilter::try_new`] for a fallible version.
+    pub fn new(items_count: usize, fp_rate: f64) -> Self {
+        params::expect_valid(Self::try_new(items_count, fp_rate), "bloom filter")
+    }
+
+    /// Fallible version of [`BlockedBloomFilter::new`], see [`BloomFilter::try_new`](crate::BloomFilter::try_new)
+    /// for the possible errors.
+    pub fn try_new(items_count: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        let params = blocked_params(items_count, fp_rate, BLOCK_BITS)?;
+        Ok(Self::with_params(params, SeededState::new()))
+    }
+
+    /// Create a new BlockedBloomFilter hashing items deterministically from `seed`, see
+    /// [`BloomFilter::with_seed`](crate::BloomFilter::with_seed).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`BlockedBloomFilter::try_with_seed`] for a fallible version.
+    pub fn with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Self {
+        params::expect_valid(
+            Self::try_with_seed(items_count, fp_rate, seed),
+            "bloom filter",
+        )
+    }
+
+    /// Fallible version of [`BlockedBloomFilter::with_seed`], see [`BloomFilter::try_new`](crate::BloomFilter::try_new)
+    /// for the possible errors.
+    pub fn try_with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Result<Self, BloomError> {
+        let params = blocked_params(items_count, fp_rate, BLOCK_BITS)?;
+        Ok(Self::with_params(params, SeededState::with_seed(seed)))
+    }
+}
+
+impl<T: ?Sized + Hash, S: BuildHasher + 'static> BlockedBloomFilter<T, S> {
+    /// Create a new BlockedBloomFilter sized by `params`, hashing items with hashers built by `hash_builder`.
+    ///
+    /// The number of bits is rounded up to a whole number of 512-bit blocks. Parameters sized for a standard
+    /// filter, e.g. by [`BloomParams::from_items_and_fp_rate`], give a higher false positive rate than expected.
+    pub fn with_params(params: BloomParams, hash_builder: S) -> Self {
+        BlockedBloomFilter {
+            blocks: vec![Block::default(); params.num_bits().div_ceil(BLOCK_BITS)],
+            optimal_k: params.num_hashes(),
+            capacity: params.items_count(),
+            inserted_count: 0,
+            hash_builder,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Insert an element into the filter.
+    pub fn insert(&mut self, item: &T) {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        let mask = self.mask(h2);
+        let len = self.blocks.len();
+        let block = &mut self.blocks[fast_range(h1, len)];
+        for (word, mask) in block.0.iter_mut().zip(mask) {
+            *word |= mask;
+        }
+        self.inserted_count += 1;
+    }
+
+    /// Checks if an element is contained in the filter, see [`BloomFilter::contains`](crate::BloomFilter::contains).
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        let mask = self.mask(h2);
+        let block = &self.blocks[fast_range(h1, self.blocks.len())];
+        block
+            .0
+            .iter()
+            .zip(mask)
+            .all(|(word, mask)| word & mask == mask)
+    }
+
+    /// The bits of an item within its block.
+    fn mask(&self, h2: u64) -> [u64; BLOCK_WORDS] {
+        let mut mask = [0; BLOCK_WORDS];
+        for k_i in 0..self.optimal_k {
+            let bit = bit_in_block(h2, k_i, BLOCK_BITS);
+            mask[bit / 64] |= 1 << (bit % 64);
+        }
+        mask
+    }
+}
+
+impl<T: ?Sized, S> BlockedBloomFilter<T, S> {
+    /// The number of bits of this filter.
+    pub fn num_bits(&self) -> usize {
+        self.blocks.len() * BLOCK_BITS
+    }
+
+    /// The number of 512-bit blocks of this filter.
+    pub fn num_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// The number of hash functions of this filter.
+    pub fn num_hashes(&self) -> u32 {
+        self.optimal_k
+    }
+
+    /// The number of items this filter was sized for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of insertions performed on this filter, including repeated insertions of the same item.
+    pub fn inserted_count(&self) -> u64 {
+        self.inserted_count
+    }
+
+    /// The hash builder used by this filter.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+}
+
+impl<T: ?Sized + Hash> RegisterBlockedBloomFilter<T> {
+    /// Create a new RegisterBlockedBloomFilter based on its size and the expected false positive rate, hashing
+    /// items with a random seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`RegisterBlockedBloomFilter::try_new`] for a fallible version.
+    pub fn new(items_count: usize, fp_rate: f64) -> Self {
+        params::expect_valid(Self::try_new(items_count, fp_rate), "bloom filter")
+    }
+
+    /// Fallible version of [`RegisterBlockedBloomFilter::new`], see
+    /// [`BloomFilter::try_new`](crate::BloomFilter::try_new) for the possible errors.
+    pub fn try_new(items_count: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        let params = blocked_params(items_count, fp_rate, u64::BITS as usize)?;
+        Ok(Self::with_params(params, SeededState::new()))
+    }
+
+    /// Create a new RegisterBlockedBloomFilter hashing items deterministically from `seed`, see
+    /// [`BloomFilter::with_seed`](crate::BloomFilter::with_seed).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`RegisterBlockedBloomFilter::try_with_seed`] for a fallible version.
+    pub fn with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Self {
+        params::expect_valid(
+            Self::try_with_seed(items_count, fp_rate, seed),
+            "bloom filter",
+        )
+    }
+
+    /// Fallible version of [`RegisterBlockedBloomFilter::with_seed`], see
+    /// [`BloomFilter::try_new`](crate::BloomFilter::try_new) for the possible errors.
+    pub fn try_with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Result<Self, BloomError> {
+        let params = blocked_params(items_count, fp_rate, u64::BITS as usize)?;
+        Ok(Self::with_params(params, SeededState::with_seed(seed)))
+    }
+}
+
+impl<T: ?Sized + Hash, S: BuildHasher + 'static> RegisterBlockedBloomFilter<T, S> {
+    /// Create a new RegisterBlockedBloomFilter sized by `params`, hashing items with hashers built by
+    /// `hash_builder`.
+    ///
+    /// The number of bits is rounded up to a whole number of 64-bit words, and the number of hash functions is
+    /// capped to 64. Parameters sized for a standard filter, e.g. by [`BloomParams::from_items_and_fp_rate`], give
+    /// a much higher false positive rate than expected.
+    pub fn with_params(params: BloomParams, hash_builder: S) -> Self {
+        RegisterBlockedBloomFilter {
+            words: vec![0; params.num_bits().div_ceil(u64::BITS as usize)],
+            optimal_k: params.num_hashes().min(u64::BITS),
+            capacity: params.items_count(),
+            inserted_count: 0,
+            hash_builder,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Insert an element into the filter.
+    pub fn insert(&mut self, item: &T) {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        let mask = self.mask(h2);
+        let len = self.words.len();
+        self.words[fast_range(h1, len)] |= mask;
+        self.inserted_count += 1;
+    }
+
+    /// Checks if an element is contained in the filter, see [`BloomFilter::contains`](crate::BloomFilter::contains).
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        let mask = self.mask(h2);
+        self.words[fast_range(h1, self.words.len())] & mask == mask
+    }
+
+    /// The bits of an item within its word.
+    fn mask(&self, h2: u64) -> u64 {
+        (0..self.optimal_k).fold(0, |mask, k_i| {
+            mask | 1 << bit_in_block(h2, k_i, u64::BITS as usize)
+        })
+    }
+}
+
+impl<T: ?Sized, S> RegisterBlockedBloomFilter<T, S> {
+    /// The number of bits of this filter.
+    pub fn num_bits(&self) -> usize {
+        self.words.len() * u64::BITS as usize
+    }
+
+    /// The number of hash functions of this filter.
+    pub fn num_hashes(&self) -> u32 {
+        self.optimal_k
+    }
+
+    /// The number of items this filter was sized for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of insertions performed on this filter, including repeated insertions of the same item.
+    pub fn inserted_count(&self) -> u64 {
+        self.inserted_count
+    }
+
+    /// The hash builder used by this filter.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measure_fp_rate(contains: impl Fn(&u32) -> bool) -> f64 {
+        (100_000..600_000u32).filter(|i| contains(i)).count() as f64 / 500_000.0
+    }
+
+    #[test]
+    fn blocked() {
+        let mut bloom = BlockedBloomFilter::with_seed(100_000, 0.01, 0);
+        assert_eq!(bloom.num_bits(), bloom.num_blocks() * 512);
+        for i in 0..100_000u32 {
+            bloom.insert(&i);
+        }
+        assert!((0..100_000u32).all(|i| bloom.contains(&i)));
+
+        let fp_rate = measure_fp_rate(|i| bloom.contains(i));
+        assert!(fp_rate < 0.016, "measured fp rate {fp_rate}");
+    }
+
+    #[test]
+    fn register_blocked() {
+        let mut bloom = RegisterBlockedBloomFilter::with_seed(100_000, 0.01, 0);
+        for i in 0..100_000u32 {
+            bloom.insert(&i);
+        }
+        assert!((0..100_000u32).all(|i| bloom.contains(&i)));
+
+        let fp_rate = measure_fp_rate(|i| bloom.contains(i));
+        assert!(fp_rate < 0.05, "measured fp rate {fp_rate}");
+    }
+
+    #[test]
+    fn fp_rate_at_target() {
+        for fp_rate in [0.01, 0.001] {
+            let mut blocked = BlockedBloomFilter::with_seed(100_000, fp_rate, 0);
+            let mut register_blocked = RegisterBlockedBloomFilter::with_seed(100_000, fp_rate, 0);
+            for i in 0..100_000u32 {
+                blocked.insert(&i);
+                register_blocked.insert(&i);
+            }
+
+            let measured = measure_fp_rate(|i| blocked.contains(i));
+            assert!(
+                measured < fp_rate * 1.2,
+                "blocked: {measured} for target {fp_rate}"
+            );
+            let measured = measure_fp_rate(|i| register_blocked.contains(i));
+            assert!(
+                measured < fp_rate * 1.2,
+                "register blocked: {measured} for target {fp_rate}"
+            );
+        }
+    }
+
+    #[test]
+    fn bits_in_block() {
+        for block_bits in [64, BLOCK_BITS] {
+            let mut used = vec![false; block_bits];
+            for h2 in 0..1000 {
+                for k_i in 0..8 {
+                    used[bit_in_block(h2, k_i, block_bits)] = true;
+                }
+            }
+            assert!(used.iter().all(|&used| used));
+        }
+    }
+
+    #[test]
+    fn fast_range_bounds() {
+        assert_eq!(fast_range(0, 10), 0);
+        assert_eq!(fast_range(u64::MAX, 10), 9);
+        assert_eq!(fast_range(1 << 63, 10), 5);
+    }
+
+    #[test]
+    fn try_with_seed_rejects_invalid_parameters() {
+        assert_eq!(
+            BlockedBloomFilter::<str>::try_with_seed(0, 0.01, 0).err(),
+            Some(BloomError::ZeroItemsCount)
+        );
+        assert!(matches!(
+            BlockedBloomFilter::<str>::try_with_seed(100, f64::NAN, 0),
+            Err(BloomError::InvalidFpRate(fp_rate)) if fp_rate.is_nan()
+        ));
+        assert_eq!(
+            RegisterBlockedBloomFilter::<str>::try_with_seed(0, 0.01, 0).err(),
+            Some(BloomError::ZeroItemsCount)
+        );
+        assert!(matches!(
+            RegisterBlockedBloomFilter::<str>::try_with_seed(100, f64::NAN, 0),
+            Err(BloomError::InvalidFpRate(fp_rate)) if fp_rate.is_nan()
+        ));
+    }
+}
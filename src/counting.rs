@@ -0,0 +1,409 @@
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+use bitvec::prelude::*;
+
+use crate::{hash, params, BloomError, BloomFilter, BloomParams, SeededState};
+
+/// The number of bits of each counter of a [`CountingBloomFilter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterWidth {
+    /// 4-bit counters, saturating at 15. Four bits are enough for most workloads.
+    Four,
+    /// 8-bit counters, saturating at 255.
+    Eight,
+    /// 16-bit counters, saturating at 65535.
+    Sixteen,
+}
+
+impl CounterWidth {
+    /// The number of bits of a counter.
+    pub fn bits(self) -> usize {
+        match self {
+            CounterWidth::Four => 4,
+            CounterWidth::Eight => 8,
+            CounterWidth::Sixteen => 16,
+        }
+    }
+
+    /// The value at which a counter saturates.
+    pub fn max(self) -> u16 {
+        match self {
+            CounterWidth::Four => 0xf,
+            CounterWidth::Eight => 0xff,
+            CounterWidth::Sixteen => 0xffff,
+        }
+    }
+}
+
+/// A bloom filter supporting deletion.
+///
+/// Each bit of a [`BloomFilter`] is replaced by a small counter, incremented on insertion and decremented on
+/// removal. Sizing and hashing are the same as for [`BloomFilter`], so a counting filter can be converted down to
+/// a plain filter with [`CountingBloomFilter::to_bloom_filter`].
+///
+/// Counters saturate instead of wrapping around. A saturated counter may have been incremented more times than it
+/// records, so it is never decremented again: removing items can then leave some of their counters set, which only
+/// makes the filter report more false positives. [`CountingBloomFilter::has_overflowed`] reports if this happened.
+///
+/// Example usage:
+/// ```
+/// use bloom_filter::{CounterWidth, CountingBloomFilter};
+///
+/// let mut bloom = CountingBloomFilter::new(100, 0.01, CounterWidth::Four);
+/// bloom.insert("item");
+/// assert!(bloom.contains("item"));
+/// assert!(bloom.remove("item"));
+/// assert!(!bloom.contains("item"));
+/// ```
+pub struct CountingBloomFilter<T: ?Sized, S = SeededState> {
+    counters: BitVec<u64, Lsb0>,
+    width: CounterWidth,
+    optimal_m: u64,
+    optimal_k: u32,
+    capacity: usize,
+    inserted_count: u64,
+    overflowed: bool,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized + Hash> CountingBloomFilter<T> {
+    /// Create a new CountingBloomFilter based on its size, the expected false positive rate and the width of
+    /// its counters, hashing items with a random seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`CountingBloomFilter::try_new`] for a fallible version.
+    pub fn new(items_count: usize, fp_rate: f64, width: CounterWidth) -> Self {
+        params::expect_valid(Self::try_new(items_count, fp_rate, width), "bloom filter")
+    }
+
+    /// Fallible version of [`CountingBloomFilter::new`], see [`BloomFilter::try_new`] for the possible errors.
+    pub fn try_new(
+        items_count: usize,
+        fp_rate: f64,
+        width: CounterWidth,
+    ) -> Result<Self, BloomError> {
+        let params = BloomParams::from_items_and_fp_rate(items_count, fp_rate)?;
+        Self::try_with_params(params, width, SeededState::new())
+    }
+
+    /// Create a new CountingBloomFilter hashing items deterministically from `seed`, see
+    /// [`BloomFilter::with_seed`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`CountingBloomFilter::try_with_seed`] for a fallible version.
+    pub fn with_seed(items_count: usize, fp_rate: f64, width: CounterWidth, seed: u64) -> Self {
+        params::expect_valid(
+            Self::try_with_seed(items_count, fp_rate, width, seed),
+            "bloom filter",
+        )
+    }
+
+    /// Fallible version of [`CountingBloomFilter::with_seed`], see [`BloomFilter::try_new`] for the possible
+    /// errors.
+    pub fn try_with_seed(
+        items_count: usize,
+        fp_rate: f64,
+        width: CounterWidth,
+        seed: u64,
+    ) -> Result<Self, BloomError> {
+        let params = BloomParams::from_items_and_fp_rate(items_count, fp_rate)?;
+        Self::try_with_params(params, width, SeededState::with_seed(seed))
+    }
+}
+
+impl<T: ?Sized + Hash, S: BuildHasher + 'static> CountingBloomFilter<T, S> {
+    /// Create a new CountingBloomFilter sized by `params`, hashing items with hashers built by `hash_builder`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the counters don't fit in memory, see [`CountingBloomFilter::try_with_params`] for a fallible
+    /// version.
+    pub fn with_params(params: BloomParams, width: CounterWidth, hash_builder: S) -> Self {
+        params::expect_valid(
+            Self::try_with_params(params, width, hash_builder),
+            "bloom filter",
+        )
+    }
+
+    /// Fallible version of [`CountingBloomFilter::with_params`].
+    ///
+    /// Returns an error if the counters would be too large to be allocated.
+    pub fn try_with_params(
+        params: BloomParams,
+        width: CounterWidth,
+        hash_builder: S,
+    ) -> Result<Self, BloomError> {
+        let counters_bits = params
+            .num_bits()
+            .checked_mul(width.bits())
+            .filter(|bits| *bits <= BitSlice::<u64, Lsb0>::MAX_BITS)
+            .ok_or(BloomError::BitmapSizeOverflow)?;
+
+        Ok(CountingBloomFilter {
+            counters: bitvec![u64, Lsb0; 0; counters_bits],
+            width,
+            optimal_m: params.num_bits() as u64,
+            optimal_k: params.num_hashes(),
+            capacity: params.items_count(),
+            inserted_count: 0,
+            overflowed: false,
+            hash_builder,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Insert an element into the filter, incrementing its counters.
+    pub fn insert(&mut self, item: &T) {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+
+        for k_i in 0..self.optimal_k {
+            let index = hash::get_index(h1, h2, k_i as u64, self.optimal_m);
+            let counter = self.counter(index);
+            if counter == self.width.max() {
+                self.overflowed = true;
+            } else {
+                self.set_counter(index, counter + 1);
+            }
+        }
+        self.inserted_count += 1;
+    }
+
+    /// Remove an element from the filter, decrementing its counters.
+    ///
+    /// Returns false and leaves the filter untouched if the element is not in the filter. Removing an element
+    /// which was never inserted but is reported as present because of a false positive corrupts the filter:
+    /// it may then report false negatives for other items.
+    pub fn remove<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        if !self.contains_hashes(h1, h2) {
+            return false;
+        }
+
+        for k_i in 0..self.optimal_k {
+            let index = hash::get_index(h1, h2, k_i as u64, self.optimal_m);
+            let counter = self.counter(index);
+            // a saturated counter has lost track of its actual count, so it stays saturated forever. The
+            // counter may also already be zero if several hash functions of the item share the same index.
+            if counter != self.width.max() && counter > 0 {
+                self.set_counter(index, counter - 1);
+            }
+        }
+        self.inserted_count = self.inserted_count.saturating_sub(1);
+        true
+    }
+
+    /// Checks if an element is contained in the filter, see [`BloomFilter::contains`].
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        self.contains_hashes(h1, h2)
+    }
+
+    /// An upper bound of the number of times an element was inserted in the filter (minus the number of times
+    /// it was removed), i.e. the smallest of its counters.
+    pub fn count_estimate<Q: ?Sized + Hash>(&self, item: &Q) -> u16
+    where
+        T: Borrow<Q>,
+    {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        (0..self.optimal_k)
+            .map(|k_i| self.counter(hash::get_index(h1, h2, k_i as u64, self.optimal_m)))
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn contains_hashes(&self, h1: u64, h2: u64) -> bool {
+        (0..self.optimal_k)
+            .all(|k_i| self.counter(hash::get_index(h1, h2, k_i as u64, self.optimal_m)) > 0)
+    }
+}
+
+impl<T: ?Sized, S> CountingBloomFilter<T, S> {
+    /// The number of counters of this filter, i.e. the number of bits of the equivalent [`BloomFilter`].
+    pub fn num_counters(&self) -> usize {
+        self.optimal_m as usize
+    }
+
+    /// The number of hash functions of this filter.
+    pub fn num_hashes(&self) -> u32 {
+        self.optimal_k
+    }
+
+    /// The number of items this filter was sized for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of insertions performed on this filter minus the number of successful removals.
+    pub fn inserted_count(&self) -> u64 {
+        self.inserted_count
+    }
+
+    /// The width of the counters of this filter.
+    pub fn counter_width(&self) -> CounterWidth {
+        self.width
+    }
+
+    /// Whether any counter ever saturated, in which case removals may not clear all the counters of an item.
+    pub fn has_overflowed(&self) -> bool {
+        self.overflowed
+    }
+
+    /// The hash builder used by this filter.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Convert this filter into a plain [`BloomFilter`] containing the same items, with a bit set for every
+    /// non zero counter.
+    pub fn to_bloom_filter(&self) -> BloomFilter<T, S>
+    where
+        S: Clone,
+    {
+        let bitmap: BitVec<u64, Lsb0> = (0..self.num_counters())
+            .map(|index| self.counter(index) > 0)
+            .collect();
+        BloomFilter {
+            bitmap,
+            optimal_m: self.optimal_m,
+            optimal_k: self.optimal_k,
+            capacity: self.capacity,
+            inserted_count: self.inserted_count,
+            hash_builder: self.hash_builder.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    fn counter(&self, index: usize) -> u16 {
+        let bits = self.width.bits();
+        self.counters[index * bits..(index + 1) * bits].load_le()
+    }
+
+    fn set_counter(&mut self, index: usize, value: u16) {
+        let bits = self.width.bits();
+        self.counters[index * bits..(index + 1) * bits].store_le(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_remove() {
+        for width in [
+            CounterWidth::Four,
+            CounterWidth::Eight,
+            CounterWidth::Sixteen,
+        ] {
+            let mut bloom = CountingBloomFilter::with_seed(1000, 0.01, width, 0);
+            for i in 0..1000u32 {
+                bloom.insert(&i);
+            }
+            for i in 0..500u32 {
+                assert!(bloom.remove(&i));
+            }
+            for i in 500..1000u32 {
+                assert!(bloom.contains(&i));
+            }
+            let remaining = (0..500u32).filter(|i| bloom.contains(i)).count();
+            assert!(remaining < 20, "{remaining} removed items still present");
+            assert!(!bloom.has_overflowed());
+        }
+    }
+
+    #[test]
+    fn remove_absent_item() {
+        let mut bloom = CountingBloomFilter::with_seed(100, 0.01, CounterWidth::Four, 0);
+        bloom.insert("item");
+        assert!(!bloom.remove("other_item"));
+        assert!(bloom.contains("item"));
+    }
+
+    #[test]
+    fn count_estimate() {
+        let mut bloom = CountingBloomFilter::with_seed(100, 0.01, CounterWidth::Eight, 0);
+        assert_eq!(bloom.count_estimate("item"), 0);
+        for _ in 0..3 {
+            bloom.insert("item");
+        }
+        assert_eq!(bloom.count_estimate("item"), 3);
+        bloom.remove("item");
+        assert_eq!(bloom.count_estimate("item"), 2);
+    }
+
+    #[test]
+    fn saturation() {
+        let mut bloom = CountingBloomFilter::with_seed(100, 0.01, CounterWidth::Four, 0);
+        for _ in 0..20 {
+            bloom.insert("item");
+        }
+        assert!(bloom.has_overflowed());
+        assert_eq!(bloom.count_estimate("item"), 15);
+
+        // saturated counters are never decremented
+        for _ in 0..20 {
+            bloom.remove("item");
+        }
+        assert_eq!(bloom.count_estimate("item"), 15);
+        assert!(bloom.contains("item"));
+    }
+
+    #[test]
+    fn to_bloom_filter() {
+        let mut counting = CountingBloomFilter::with_seed(1000, 0.01, CounterWidth::Four, 3);
+        let mut bloom = BloomFilter::with_seed(1000, 0.01, 3);
+        for i in 0..1000u32 {
+            counting.insert(&i);
+            bloom.insert(&i);
+        }
+        for i in 1000..1500u32 {
+            counting.insert(&i);
+            counting.remove(&i);
+        }
+
+        let converted = counting.to_bloom_filter();
+        assert_eq!(converted.bitmap, bloom.bitmap);
+        assert!(converted.is_compatible(&bloom));
+        assert_eq!(converted.capacity(), 1000);
+        assert_eq!(converted.inserted_count(), 1000);
+    }
+
+    #[test]
+    fn try_new_rejects_invalid_parameters() {
+        assert_eq!(
+            CountingBloomFilter::<str>::try_new(0, 0.01, CounterWidth::Four).err(),
+            Some(BloomError::ZeroItemsCount)
+        );
+        assert_eq!(
+            CountingBloomFilter::<str>::try_new(100, 1.0, CounterWidth::Four).err(),
+            Some(BloomError::InvalidFpRate(1.0))
+        );
+        assert_eq!(
+            CountingBloomFilter::<str>::try_with_seed(100, 0.0, CounterWidth::Four, 0).err(),
+            Some(BloomError::InvalidFpRate(0.0))
+        );
+        let params = BloomParams::from_bits_and_hashes(BitSlice::<u64, Lsb0>::MAX_BITS, 7).unwrap();
+        assert_eq!(
+            CountingBloomFilter::<str>::try_with_params(
+                params,
+                CounterWidth::Four,
+                SeededState::new()
+            )
+            .err(),
+            Some(BloomError::BitmapSizeOverflow)
+        );
+    }
+}
@@ -0,0 +1,456 @@
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+use bitvec::prelude::*;
+
+use crate::{hash, params, BloomError, SeededState};
+
+/// Default number of fingerprints per bucket, which allows load factors of about 95%.
+const DEFAULT_BUCKET_SIZE: usize = 4;
+/// Maximum number of fingerprints relocated to make room for a new one before giving up.
+const MAX_KICKS: usize = 500;
+
+/// A cuckoo filter, a membership structure supporting deletions.
+///
+/// Items are stored as short fingerprints of `fingerprint_bits` bits, in an array of buckets of `bucket_size`
+/// slots. Each item can live in two buckets: the second one is derived from the first one and the fingerprint only
+/// (partial-key cuckoo hashing), so that fingerprints can be moved between their two buckets to make room for new
+/// items without knowing the items they come from.
+///
+/// For false positive rates below 3%, a cuckoo filter takes less space than a [`BloomFilter`](crate::BloomFilter),
+/// and much less than a [`CountingBloomFilter`](crate::CountingBloomFilter) while also supporting deletions. Unlike
+/// bloom filters, it can get full: [`CuckooFilter::insert`] then returns an error.
+///
+/// Example usage:
+/// ```
+/// use bloom_filter::CuckooFilter;
+///
+/// let mut cuckoo = CuckooFilter::<str>::new(100, 0.01);
+/// cuckoo.insert("item").unwrap();
+/// assert!(cuckoo.contains("item"));
+/// assert!(cuckoo.remove("item"));
+/// assert!(!cuckoo.contains("item"));
+/// ```
+pub struct CuckooFilter<T: ?Sized, S = SeededState> {
+    slots: BitVec<u64, Lsb0>,
+    num_buckets: usize,
+    bucket_size: usize,
+    fingerprint_bits: u32,
+    len: usize,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized + Hash> CuckooFilter<T> {
+    /// Create a new CuckooFilter able to hold `items_count` items with the expected false positive rate, hashing
+    /// items with a random seed.
+    ///
+    /// Buckets hold 4 fingerprints, and fingerprints are just long enough to reach `fp_rate` once the filter is
+    /// full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`CuckooFilter::try_new`] for a fallible version.
+    pub fn new(items_count: usize, fp_rate: f64) -> Self {
+        params::expect_valid(Self::try_new(items_count, fp_rate), "cuckoo filter")
+    }
+
+    /// Fallible version of [`CuckooFilter::new`].
+    ///
+    /// Returns an error if `items_count` is zero, if `fp_rate` is not in the open interval `(0, 1)` or needs
+    /// fingerprints longer than 32 bits, or if the filter would be too large to be allocated.
+    pub fn try_new(items_count: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        let fingerprint_bits = fingerprint_bits_for(fp_rate, DEFAULT_BUCKET_SIZE)?;
+        Self::try_with_options(
+            items_count,
+            fingerprint_bits,
+            DEFAULT_BUCKET_SIZE,
+            SeededState::new(),
+        )
+    }
+
+    /// Create a new CuckooFilter hashing items deterministically from `seed`, see
+    /// [`BloomFilter::with_seed`](crate::BloomFilter::with_seed).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`CuckooFilter::try_with_seed`] for a fallible version.
+    pub fn with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Self {
+        params::expect_valid(
+            Self::try_with_seed(items_count, fp_rate, seed),
+            "cuckoo filter",
+        )
+    }
+
+    /// Fallible version of [`CuckooFilter::with_seed`], see [`CuckooFilter::try_new`] for the possible errors.
+    pub fn try_with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Result<Self, BloomError> {
+        let fingerprint_bits = fingerprint_bits_for(fp_rate, DEFAULT_BUCKET_SIZE)?;
+        Self::try_with_options(
+            items_count,
+            fingerprint_bits,
+            DEFAULT_BUCKET_SIZE,
+            SeededState::with_seed(seed),
+        )
+    }
+}
+
+impl<T: ?Sized + Hash, S: BuildHasher + 'static> CuckooFilter<T, S> {
+    /// Create a new CuckooFilter able to hold `items_count` items, with explicit fingerprint and bucket sizes,
+    /// hashing items with hashers built by `hash_builder`.
+    ///
+    /// Once the filter holds as many items as it can, its false positive rate is about
+    /// `2 * bucket_size / 2^fingerprint_bits`. Larger buckets allow higher load factors (about 50% of the slots
+    /// can be filled with buckets of 1 fingerprint, 84% with 2 and 95% with 4) but need longer fingerprints for
+    /// the same false positive rate. The number of buckets is rounded up to a power of two.
+    ///
+    /// Returns an error if `items_count` is zero, if `fingerprint_bits` is not in `1..=32`, if `bucket_size` is
+    /// not in `1..=8` or if the filter would be too large to be allocated.
+    pub fn try_with_options(
+        items_count: usize,
+        fingerprint_bits: u32,
+        bucket_size: usize,
+        hash_builder: S,
+    ) -> Result<Self, BloomError> {
+        params::check_items_count(items_count)?;
+        if !(1..=32).contains(&fingerprint_bits) {
+            return Err(BloomError::InvalidFingerprintBits(fingerprint_bits));
+        }
+        if !(1..=8).contains(&bucket_size) {
+            return Err(BloomError::InvalidBucketSize(bucket_size));
+        }
+
+        let num_buckets = ((items_count as f64
+            / (bucket_size as f64 * max_load_factor(bucket_size)))
+        .ceil() as usize)
+            .checked_next_power_of_two()
+            .ok_or(BloomError::BitmapSizeOverflow)?;
+        let slots_bits = num_buckets
+            .checked_mul(bucket_size * fingerprint_bits as usize)
+            .filter(|bits| *bits <= BitSlice::<u64, Lsb0>::MAX_BITS)
+            .ok_or(BloomError::BitmapSizeOverflow)?;
+
+        Ok(CuckooFilter {
+            slots: bitvec![u64, Lsb0; 0; slots_bits],
+            num_buckets,
+            bucket_size,
+            fingerprint_bits,
+            len: 0,
+            hash_builder,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Insert an element into the filter.
+    ///
+    /// Inserting the same element several times stores several copies of its fingerprint, which must all be
+    /// removed for the element to be removed. Returns [`BloomError::FilterFull`] and leaves the filter untouched if
+    /// no room could be made for the element.
+    pub fn insert(&mut self, item: &T) -> Result<(), BloomError> {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        let (mut fingerprint, index) = self.fingerprint_and_index(h1, h2);
+        let alt_index = self.alt_index(index, fingerprint);
+        if self.store(index, fingerprint) || self.store(alt_index, fingerprint) {
+            self.len += 1;
+            return Ok(());
+        }
+
+        // both buckets are full: evict fingerprints to their other bucket until one lands in a free slot,
+        // remembering the evictions to revert them if this doesn't happen
+        let mut rng = h1 | 1;
+        let mut index = if h2 & 1 == 0 { index } else { alt_index };
+        let mut evictions = Vec::new();
+        for _ in 0..MAX_KICKS {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            let slot = (rng % self.bucket_size as u64) as usize;
+            let evicted = self.slot(index, slot);
+            self.set_slot(index, slot, fingerprint);
+            evictions.push((index, slot, evicted));
+
+            fingerprint = evicted;
+            index = self.alt_index(index, fingerprint);
+            if self.store(index, fingerprint) {
+                self.len += 1;
+                return Ok(());
+            }
+        }
+
+        for (index, slot, evicted) in evictions.into_iter().rev() {
+            self.set_slot(index, slot, evicted);
+        }
+        Err(BloomError::FilterFull)
+    }
+
+    /// Checks if an element is contained in the filter, see [`BloomFilter::contains`](crate::BloomFilter::contains).
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        let (fingerprint, index) = self.fingerprint_and_index(h1, h2);
+        self.find(index, fingerprint).is_some()
+            || self
+                .find(self.alt_index(index, fingerprint), fingerprint)
+                .is_some()
+    }
+
+    /// Remove an element from the filter, returning whether it was found.
+    ///
+    /// Only elements which were inserted before can be removed: removing an element which is only a false
+    /// positive of the filter removes the fingerprint of another element, which is then no longer found.
+    pub fn remove<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        let (fingerprint, index) = self.fingerprint_and_index(h1, h2);
+        for index in [index, self.alt_index(index, fingerprint)] {
+            if let Some(slot) = self.find(index, fingerprint) {
+                self.set_slot(index, slot, 0);
+                self.len -= 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The fingerprint of an element, never 0 which marks empty slots, and its first bucket.
+    fn fingerprint_and_index(&self, h1: u64, h2: u64) -> (u32, usize) {
+        let fingerprint = (h2 % ((1 << self.fingerprint_bits) - 1)) as u32 + 1;
+        (fingerprint, h1 as usize & (self.num_buckets - 1))
+    }
+
+    /// The other bucket of a fingerprint stored in the bucket `index`.
+    ///
+    /// This is an involution, so the first bucket of a fingerprint is also the other bucket of its second one.
+    fn alt_index(&self, index: usize, fingerprint: u32) -> usize {
+        let hash = (fingerprint as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        (index ^ (hash ^ (hash >> 32)) as usize) & (self.num_buckets - 1)
+    }
+}
+
+impl<T: ?Sized, S> CuckooFilter<T, S> {
+    /// The number of items in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the filter holds no item.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of fingerprint slots of this filter, which bounds the number of items it can hold.
+    pub fn capacity(&self) -> usize {
+        self.num_buckets * self.bucket_size
+    }
+
+    /// The proportion of slots of the filter holding a fingerprint, between 0 and 1.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / self.capacity() as f64
+    }
+
+    /// The number of bits of the fingerprints of this filter.
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.fingerprint_bits
+    }
+
+    /// The number of fingerprints each bucket of this filter holds.
+    pub fn bucket_size(&self) -> usize {
+        self.bucket_size
+    }
+
+    /// The number of buckets of this filter.
+    pub fn num_buckets(&self) -> usize {
+        self.num_buckets
+    }
+
+    /// The hash builder used by this filter.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Store a fingerprint in a free slot of the bucket `index`, returning whether there was one.
+    fn store(&mut self, index: usize, fingerprint: u32) -> bool {
+        match self.find(index, 0) {
+            Some(slot) => {
+                self.set_slot(index, slot, fingerprint);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The first slot of the bucket `index` holding `fingerprint`.
+    fn find(&self, index: usize, fingerprint: u32) -> Option<usize> {
+        (0..self.bucket_size).find(|&slot| self.slot(index, slot) == fingerprint)
+    }
+
+    fn slot(&self, index: usize, slot: usize) -> u32 {
+        let bits = self.fingerprint_bits as usize;
+        let start = (index * self.bucket_size + slot) * bits;
+        self.slots[start..start + bits].load_le()
+    }
+
+    fn set_slot(&mut self, index: usize, slot: usize, fingerprint: u32) {
+        let bits = self.fingerprint_bits as usize;
+        let start = (index * self.bucket_size + slot) * bits;
+        self.slots[start..start + bits].store_le(fingerprint);
+    }
+}
+
+/// The load factor a cuckoo filter with buckets of `bucket_size` fingerprints can reliably reach.
+fn max_load_factor(bucket_size: usize) -> f64 {
+    match bucket_size {
+        1 => 0.5,
+        2 => 0.84,
+        3 => 0.9,
+        _ => 0.95,
+    }
+}
+
+/// The smallest number of fingerprint bits reaching `fp_rate` with buckets of `bucket_size` fingerprints.
+fn fingerprint_bits_for(fp_rate: f64, bucket_size: usize) -> Result<u32, BloomError> {
+    // a lookup compares the fingerprint of an item with the 2 * bucket_size slots of its two buckets
+    params::fingerprint_bits(fp_rate, 2 * bucket_size, 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_remove() {
+        let mut cuckoo = CuckooFilter::with_seed(1000, 0.001, 0);
+        for i in 0..1000u32 {
+            cuckoo.insert(&i).unwrap();
+        }
+        assert_eq!(cuckoo.len(), 1000);
+        assert!((0..1000u32).all(|i| cuckoo.contains(&i)));
+
+        for i in 0..500u32 {
+            assert!(cuckoo.remove(&i));
+        }
+        assert_eq!(cuckoo.len(), 500);
+        assert!((500..1000u32).all(|i| cuckoo.contains(&i)));
+        let still_contained = (0..500u32).filter(|i| cuckoo.contains(i)).count();
+        assert!(still_contained < 5, "{still_contained}");
+
+        for i in 500..1000u32 {
+            assert!(cuckoo.remove(&i));
+        }
+        assert!(cuckoo.is_empty());
+        assert!(cuckoo.slots.not_any());
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut cuckoo = CuckooFilter::with_seed(100, 0.01, 0);
+        cuckoo.insert("item").unwrap();
+        cuckoo.insert("item").unwrap();
+        assert_eq!(cuckoo.len(), 2);
+        assert!(cuckoo.remove("item"));
+        assert!(cuckoo.contains("item"));
+        assert!(cuckoo.remove("item"));
+        assert!(!cuckoo.contains("item"));
+        assert!(!cuckoo.remove("item"));
+    }
+
+    #[test]
+    fn alt_index_is_an_involution() {
+        let cuckoo =
+            CuckooFilter::<u32>::try_with_options(1000, 12, 4, SeededState::with_seed(0)).unwrap();
+        for index in 0..cuckoo.num_buckets() {
+            for fingerprint in 1..100 {
+                let alt_index = cuckoo.alt_index(index, fingerprint);
+                assert_eq!(cuckoo.alt_index(alt_index, fingerprint), index);
+            }
+        }
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        for fp_rate in [0.05, 0.01, 0.001] {
+            let mut cuckoo = CuckooFilter::with_seed(10_000, fp_rate, 0);
+            for i in 0..10_000u32 {
+                cuckoo.insert(&i).unwrap();
+            }
+            let false_positives = (10_000..210_000u32).filter(|i| cuckoo.contains(i)).count();
+            let measured = false_positives as f64 / 200_000.0;
+            assert!(
+                measured < fp_rate,
+                "measured fp rate {measured} for target {fp_rate}"
+            );
+        }
+    }
+
+    #[test]
+    fn load_factor() {
+        for (bucket_size, min_load_factor) in [(1, 0.4), (2, 0.8), (4, 0.93), (8, 0.97)] {
+            let mut cuckoo =
+                CuckooFilter::try_with_options(10_000, 16, bucket_size, SeededState::with_seed(0))
+                    .unwrap();
+            let mut inserted = 0u32;
+            while cuckoo.insert(&inserted).is_ok() {
+                inserted += 1;
+            }
+            let load_factor = cuckoo.load_factor();
+            assert!(
+                load_factor > min_load_factor,
+                "load factor {load_factor} with buckets of {bucket_size}"
+            );
+
+            // a failed insertion leaves the filter untouched
+            assert_eq!(cuckoo.len(), inserted as usize);
+            assert!((0..inserted).all(|i| cuckoo.contains(&i)));
+        }
+    }
+
+    #[test]
+    fn sized_for_items_count() {
+        let mut cuckoo = CuckooFilter::with_seed(10_000, 0.01, 0);
+        assert_eq!(cuckoo.bucket_size(), 4);
+        assert_eq!(cuckoo.fingerprint_bits(), 10);
+        assert!(cuckoo.capacity() >= 10_000);
+        for i in 0..10_000u32 {
+            cuckoo.insert(&i).unwrap();
+        }
+    }
+
+    #[test]
+    fn try_new_rejects_invalid_parameters() {
+        assert_eq!(
+            CuckooFilter::<str>::try_new(0, 0.01).err(),
+            Some(BloomError::ZeroItemsCount)
+        );
+        assert_eq!(
+            CuckooFilter::<str>::try_new(100, 0.0).err(),
+            Some(BloomError::InvalidFpRate(0.0))
+        );
+        assert_eq!(
+            CuckooFilter::<str>::try_new(100, 1e-12).err(),
+            Some(BloomError::InvalidFpRate(1e-12))
+        );
+        assert_eq!(
+            CuckooFilter::<str>::try_with_seed(0, 0.01, 0).err(),
+            Some(BloomError::ZeroItemsCount)
+        );
+        let state = SeededState::with_seed(0);
+        assert_eq!(
+            CuckooFilter::<str>::try_with_options(100, 33, 4, state).err(),
+            Some(BloomError::InvalidFingerprintBits(33))
+        );
+        assert_eq!(
+            CuckooFilter::<str>::try_with_options(100, 16, 0, state).err(),
+            Some(BloomError::InvalidBucketSize(0))
+        );
+        assert_eq!(
+            CuckooFilter::<str>::try_with_options(usize::MAX, 16, 4, state).err(),
+            Some(BloomError::BitmapSizeOverflow)
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::fmt;
+
+/// Errors returned when building filters from invalid parameters, inserting into full filters or combining filters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BloomError {
+    /// The expected number of items must be strictly positive.
+    ZeroItemsCount,
+    /// The false positive rate must lie in the open interval `(0, 1)`.
+    InvalidFpRate(f64),
+    /// The number of bits of the filter must be strictly positive.
+    ZeroBitsCount,
+    /// The number of hash functions of the filter must be strictly positive.
+    ZeroHashesCount,
+    /// The filter is too small to hold a single item at the requested false positive rate.
+    CapacityTooSmall,
+    /// The number of bits needed by the filter does not fit in memory.
+    BitmapSizeOverflow,
+    /// The growth factor of a scalable filter must be finite and greater than 1.
+    InvalidGrowthFactor(f64),
+    /// The tightening ratio of a scalable filter must lie in the open interval `(0, 1)`.
+    InvalidTighteningRatio(f64),
+    /// The size of a split block filter must be a positive multiple of 32 bytes, no larger than 128 MiB.
+    InvalidNumBytes(usize),
+    /// The fingerprints of a cuckoo filter and the results of a ribbon filter must be between 1 and 32 bits long.
+    InvalidFingerprintBits(u32),
+    /// The buckets of a cuckoo filter must hold between 1 and 8 fingerprints.
+    InvalidBucketSize(usize),
+    /// The quotients of a quotient filter must be between 1 and 63 bits long.
+    InvalidQuotientBits(u32),
+    /// The remainders of a quotient filter must be at least 1 bit long, and at most 64 bits minus the quotient bits.
+    InvalidRemainderBits(u32),
+    /// The bands of a ribbon filter must be between 32 and 128 coefficients wide.
+    InvalidBandWidth(u32),
+    /// The filter has no room left for the item.
+    FilterFull,
+    /// No static filter could be built from the keys, which should only happen with adversarial keys.
+    ConstructionFailed,
+    /// The filters don't share the same number of bits, hash functions and hashers.
+    IncompatibleFilters,
+}
+
+impl fmt::Display for BloomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BloomError::ZeroItemsCount => write!(f, "items count must be greater than zero"),
+            BloomError::InvalidFpRate(fp_rate) => {
+                write!(f, "false positive rate must be in (0, 1), got {fp_rate}")
+            }
+            BloomError::ZeroBitsCount => write!(f, "bits count must be greater than zero"),
+            BloomError::ZeroHashesCount => write!(f, "hashes count must be greater than zero"),
+            BloomError::CapacityTooSmall => write!(
+                f,
+                "not enough bits to hold a single item at the requested false positive rate"
+            ),
+            BloomError::BitmapSizeOverflow => {
+                write!(f, "bitmap size overflows the addressable number of bits")
+            }
+            BloomError::InvalidGrowthFactor(growth_factor) => {
+                write!(
+                    f,
+                    "growth factor must be greater than 1, got {growth_factor}"
+                )
+            }
+            BloomError::InvalidTighteningRatio(tightening_ratio) => write!(
+                f,
+                "tightening ratio must be in (0, 1), got {tightening_ratio}"
+            ),
+            BloomError::InvalidNumBytes(num_bytes) => write!(
+                f,
+                "number of bytes must be a positive multiple of 32 no larger than 128 MiB, got {num_bytes}"
+            ),
+            BloomError::InvalidFingerprintBits(fingerprint_bits) => write!(
+                f,
+                "fingerprint bits must be in 1..=32, got {fingerprint_bits}"
+            ),
+            BloomError::InvalidBucketSize(bucket_size) => {
+                write!(f, "bucket size must be in 1..=8, got {bucket_size}")
+            }
+            BloomError::InvalidQuotientBits(quotient_bits) => {
+                write!(f, "quotient bits must be in 1..=63, got {quotient_bits}")
+            }
+            BloomError::InvalidRemainderBits(remainder_bits) => write!(
+                f,
+                "remainder bits must be positive and fit in 64 bits with the quotient, got {remainder_bits}"
+            ),
+            BloomError::InvalidBandWidth(band_width) => {
+                write!(f, "band width must be in 32..=128, got {band_width}")
+            }
+            BloomError::FilterFull => write!(f, "the filter is full"),
+            BloomError::ConstructionFailed => {
+                write!(f, "failed to build a static filter from the keys")
+            }
+            BloomError::IncompatibleFilters => write!(
+                f,
+                "filters must share the same number of bits, hash functions and hashers"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BloomError {}
@@ -0,0 +1,181 @@
+use std::{
+    any::Any,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash, Hasher},
+};
+
+use siphasher::{
+    sip::SipHasher13,
+    sip128::{self, Hasher128},
+};
+
+/// The default hash builder of bloom filters: SipHash-1-3 keyed from a 64-bit seed.
+///
+/// Unlike [`RandomState`], the output of the hashers built by this state is fixed by the SipHash
+/// specification and does not change between Rust releases, so filters built with the same seed are
+/// reproducible across processes and machines. The two 64-bit SipHash keys are the first two outputs of
+/// SplitMix64 seeded with the seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeededState {
+    seed: u64,
+    keys: [u64; 2],
+}
+
+impl SeededState {
+    /// Create a new state with a random seed.
+    pub fn new() -> Self {
+        Self::with_seed(RandomState::new().build_hasher().finish())
+    }
+
+    /// Create a new state hashing deterministically from `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        SeededState {
+            seed,
+            keys: sip_keys(seed),
+        }
+    }
+
+    /// The seed the hashers of this state are keyed from.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// A SipHash-1-3 hasher with the same keys as [`SeededState::build_hasher`], giving 128-bit digests.
+    fn build_hasher128(&self) -> sip128::SipHasher13 {
+        sip128::SipHasher13::new_with_keys(self.keys[0], self.keys[1])
+    }
+}
+
+impl Default for SeededState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BuildHasher for SeededState {
+    type Hasher = SipHasher13;
+
+    fn build_hasher(&self) -> SipHasher13 {
+        SipHasher13::new_with_keys(self.keys[0], self.keys[1])
+    }
+}
+
+/// Hash an item into the two 64-bit kernel hashes `h1` and `h2` all the hash functions of a filter derive from.
+pub(crate) fn hash_kernel<S: BuildHasher + 'static, T: ?Sized + Hash>(
+    hash_builder: &S,
+    item: &T,
+) -> (u64, u64) {
+    // the item is hashed only once: SeededState hashes it into a 128-bit SipHash digest, split into h1 and h2
+    if let Some(state) = (hash_builder as &dyn Any).downcast_ref::<SeededState>() {
+        let mut hasher = state.build_hasher128();
+        item.hash(&mut hasher);
+        return split_digest(&hasher);
+    }
+    let mut hasher = hash_builder.build_hasher();
+    item.hash(&mut hasher);
+    finish_twice(hasher)
+}
+
+/// Hash the exact bytes of `bytes` into the two kernel hashes, without the framing `Hash` adds to slices and strings.
+pub(crate) fn hash_bytes_kernel<S: BuildHasher + 'static>(
+    hash_builder: &S,
+    bytes: &[u8],
+) -> (u64, u64) {
+    if let Some(state) = (hash_builder as &dyn Any).downcast_ref::<SeededState>() {
+        let mut hasher = state.build_hasher128();
+        hasher.write(bytes);
+        return split_digest(&hasher);
+    }
+    let mut hasher = hash_builder.build_hasher();
+    hasher.write(bytes);
+    finish_twice(hasher)
+}
+
+fn split_digest(hasher: &sip128::SipHasher13) -> (u64, u64) {
+    let digest = hasher.finish128();
+    (digest.h1, digest.h2)
+}
+
+/// The kernel hashes of hashers with 64-bit digests.
+fn finish_twice<H: Hasher>(mut hasher: H) -> (u64, u64) {
+    // h1 is the digest of the item, and h2 the digest of the item followed by a constant byte. Both come from
+    // the whole state of the hasher, but they are only as independent as its finalization makes them: with a
+    // weak hasher (FxHash, or a hasher whose finish is the identity...) h2 can be a simple function of h1,
+    // which raises the false positive rate.
+    let hash1 = hasher.finish();
+    hasher.write_u8(1);
+    let hash2 = hasher.finish();
+
+    (hash1, hash2)
+}
+
+/// The index of the `k_i`-th hash function of an item among `m` slots, given its kernel hashes.
+pub(crate) fn get_index(h1: u64, h2: u64, k_i: u64, m: u64) -> usize {
+    // compute H_k(x) = h1(x) + k_i * h2(x) + (k_i^3 - k_i) / 6 and use it to index into the m elements of the
+    // bitvec, the cubic term keeps the indexes apart in small filters where m shares factors with h2
+    let cubic = k_i.wrapping_mul(k_i).wrapping_mul(k_i).wrapping_sub(k_i) / 6;
+    (h1.wrapping_add(k_i.wrapping_mul(h2)).wrapping_add(cubic) % m) as usize
+}
+
+/// Mix a 64-bit hash with a seed.
+///
+/// This is the finalizer of MurmurHash3 applied to `hash + seed`, which is a bijection: for a given seed, distinct
+/// hashes stay distinct.
+pub(crate) fn mix(hash: u64, seed: u64) -> u64 {
+    let mut hash = hash.wrapping_add(seed);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+/// Derive two 64-bit SipHash keys from a seed.
+///
+/// The keys are the first two outputs of SplitMix64 seeded with `seed`, which spreads close seeds
+/// (e.g. 0, 1, 2...) over unrelated keys.
+fn sip_keys(seed: u64) -> [u64; 2] {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    };
+    [next(), next()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sip_keys_are_stable() {
+        assert_eq!(sip_keys(0), [0xe220_a839_7b1d_cdaf, 0x6e78_9e6a_a1b9_65f4]);
+    }
+
+    #[test]
+    fn seeded_hashers_are_stable() {
+        let mut hasher = SeededState::with_seed(0).build_hasher();
+        hasher.write(b"bloom");
+        assert_eq!(hasher.finish(), 1_985_681_475_295_655_706);
+    }
+
+    #[test]
+    fn seeded_kernel_splits_a_128_bit_digest() {
+        let state = SeededState::with_seed(0);
+        let mut hasher = state.build_hasher128();
+        "bloom".hash(&mut hasher);
+        let digest = hasher.finish128();
+        assert_eq!(hash_kernel(&state, "bloom"), (digest.h1, digest.h2));
+
+        // other hashers are finished twice
+        let state = std::hash::BuildHasherDefault::<SipHasher13>::default();
+        let mut hasher = state.build_hasher();
+        "bloom".hash(&mut hasher);
+        let h1 = hasher.finish();
+        hasher.write_u8(1);
+        assert_eq!(hash_kernel(&state, "bloom"), (h1, hasher.finish()));
+    }
+}
@@ -1,15 +1,66 @@
 use std::{
-    collections::hash_map::{DefaultHasher, RandomState},
-    hash::{BuildHasher, Hash, Hasher},
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
     marker::PhantomData,
 };
 
 use bitvec::prelude::*;
 
+mod atomic;
+mod batch;
+mod binary_fuse;
+mod blocked;
+mod counting;
+mod cuckoo;
+mod error;
+mod hash;
+mod ops;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod params;
+mod partitioned;
+mod quotient;
+mod ribbon;
+mod scalable;
+mod serialization;
+mod split_block;
+mod stats;
+mod xor;
+
+pub use atomic::AtomicBloomFilter;
+pub use binary_fuse::{BinaryFuseFilter, BinaryFuseFilter16, BinaryFuseFilter8};
+pub use blocked::{BlockedBloomFilter, RegisterBlockedBloomFilter};
+pub use counting::{CounterWidth, CountingBloomFilter};
+pub use cuckoo::CuckooFilter;
+pub use error::BloomError;
+pub use hash::SeededState;
+pub use params::BloomParams;
+pub use partitioned::PartitionedBloomFilter;
+pub use quotient::QuotientFilter;
+pub use ribbon::{RibbonFilter, RibbonVariant};
+pub use scalable::ScalableBloomFilter;
+pub use serialization::DecodeError;
+pub use split_block::{SbbfValue, SplitBlockBloomFilter};
+pub use xor::{Fingerprint, XorFilter, XorFilter16, XorFilter8};
+
 /// A generic implementation of bloom filters
 ///
 /// This structure is generic over the type of data, and allow users to enforce a theoretical rate of false positives.
-/// The number of hash functions is derived from the expected false positive rate and the size of the filter.
+/// The number of bits and of hash functions are derived from the expected number of items and false positive rate,
+/// see [`BloomParams`] to size a filter from other constraints such as a memory budget.
+///
+/// Items are hashed by the hash builder `S`. The default, [`SeededState`], is SipHash-1-3 keyed from a 64-bit
+/// seed (see [`BloomFilter::with_seed`]), so two filters built with the same parameters and seed set the exact
+/// same bits. Any other [`BuildHasher`] (xxHash, FxHash...) can be plugged with [`BloomFilter::with_hasher`]
+/// to trade resistance to adversarial inputs for throughput.
+///
+/// Like for a `HashSet`, items can be looked up through any borrowed form of `T`: a `BloomFilter<String>` can be
+/// queried with a `&str` without allocating. As required by [`Borrow`], the borrowed form must hash like `T` itself.
+/// Insertions take a `&T`, so that the item type of a new filter is inferred from the first insertion.
+///
+/// The filter never stores items of type `T`, so it is `Send` and `Sync` as long as `S` is: a filter can be shared
+/// between threads (e.g. behind an `Arc`) and queried concurrently through [`BloomFilter::contains`],
+/// which only needs a shared reference.
 ///
 /// Example usage:
 /// ```
@@ -19,100 +70,288 @@ use bitvec::prelude::*;
 /// bloom.insert("item");
 /// assert!(bloom.contains("item"));
 /// ```
-pub struct BloomFilter<T: ?Sized> {
-    bitmap: BitVec,
+pub struct BloomFilter<T: ?Sized, S = SeededState> {
+    bitmap: BitVec<u64, Lsb0>,
     optimal_m: u64,
     optimal_k: u32,
-    hashers: [DefaultHasher; 2],
-    _marker: PhantomData<T>,
+    capacity: usize,
+    inserted_count: u64,
+    hash_builder: S,
+    // `fn(&T)` rather than `T` since no `T` is ever owned, which keeps the filter `Send + Sync` for any `T`
+    _marker: PhantomData<fn(&T)>,
 }
 
-const LN2_SQUARED: f64 = core::f64::consts::LN_2 * core::f64::consts::LN_2;
-
 impl<T: ?Sized + Hash> BloomFilter<T> {
     /// Create a new BloomFilter based on its size and the expected false positive rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`BloomFilter::try_new`] for a fallible version.
     pub fn new(items_count: usize, fp_rate: f64) -> Self {
-        // compute the optimal number of bits to use as filter size
-        let optimal_m = Self::bitmap_size(items_count, fp_rate);
-        // compute the optimal number of hash function to use
-        let optimal_k = Self::optimal_k(fp_rate);
-        // create two hashers initialized with a random state to derive all the k hashers from
-        let hashers = [
-            RandomState::new().build_hasher(),
-            RandomState::new().build_hasher(),
-        ];
+        params::expect_valid(Self::try_new(items_count, fp_rate), "bloom filter")
+    }
 
-        BloomFilter {
-            bitmap: bitvec![0; optimal_m],
-            optimal_m: optimal_m as u64,
-            optimal_k,
-            hashers,
-            _marker: PhantomData,
-        }
+    /// Create a new BloomFilter based on its size and the expected false positive rate, hashing items
+    /// with a random seed.
+    ///
+    /// Returns an error if `items_count` is zero, if `fp_rate` is not in the open interval `(0, 1)`
+    /// or if the resulting filter would be too large to be allocated.
+    pub fn try_new(items_count: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        Self::try_with_hasher(items_count, fp_rate, SeededState::new())
+    }
+
+    /// Create a new BloomFilter based on its size and the expected false positive rate, hashing items
+    /// deterministically from `seed`.
+    ///
+    /// Filters built with the same parameters and seed are identical, even across processes, machines
+    /// and Rust releases, see [`SeededState`]. Note that the bytes fed to the
+    /// hasher come from the `Hash` implementation of `T`, so types whose `Hash` depends on the platform
+    /// (like `usize` or native endian integers) only hash identically on similar platforms.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`BloomFilter::try_with_seed`] for a fallible version.
+    pub fn with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Self {
+        params::expect_valid(
+            Self::try_with_seed(items_count, fp_rate, seed),
+            "bloom filter",
+        )
+    }
+
+    /// Fallible version of [`BloomFilter::with_seed`], see [`BloomFilter::try_new`] for the possible errors.
+    pub fn try_with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Result<Self, BloomError> {
+        Self::try_with_hasher(items_count, fp_rate, SeededState::with_seed(seed))
     }
 
-    fn bitmap_size(items_count: usize, fp_rate: f64) -> usize {
-        ((-1.0f64 * items_count as f64 * fp_rate.ln()) / LN2_SQUARED).ceil() as usize
+    /// Create a new BloomFilter with exactly `num_bits` bits and `num_hashes` hash functions, hashing items
+    /// with a random seed.
+    ///
+    /// This is meant for interoperability with other implementations, [`BloomFilter::new`] should be preferred
+    /// otherwise. The [`BloomFilter::capacity`] of the filter is the number of items for which `num_hashes` is
+    /// optimal.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`BloomFilter::try_with_bits_and_hashes`] for a fallible version.
+    pub fn with_bits_and_hashes(num_bits: usize, num_hashes: u32) -> Self {
+        params::expect_valid(
+            Self::try_with_bits_and_hashes(num_bits, num_hashes),
+            "bloom filter",
+        )
     }
 
-    fn optimal_k(fp_rate: f64) -> u32 {
-        ((-1.0f64 * fp_rate.ln()) / core::f64::consts::LN_2).ceil() as u32
+    /// Fallible version of [`BloomFilter::with_bits_and_hashes`].
+    ///
+    /// Returns an error if `num_bits` or `num_hashes` is zero, or if the filter would be too large to be
+    /// allocated.
+    pub fn try_with_bits_and_hashes(num_bits: usize, num_hashes: u32) -> Result<Self, BloomError> {
+        Self::try_with_bits_hashes_and_hasher(num_bits, num_hashes, SeededState::new())
+    }
+
+    /// The seed used to key the hash functions of this filter.
+    pub fn seed(&self) -> u64 {
+        self.hash_builder.seed()
+    }
+}
+
+impl<T: ?Sized + Hash, S: BuildHasher + 'static> BloomFilter<T, S> {
+    /// Create a new BloomFilter based on its size and the expected false positive rate, hashing items
+    /// with hashers built by `hash_builder`.
+    ///
+    /// Items are hashed in a single pass. [`SeededState`] splits a 128-bit digest into the two kernel hashes,
+    /// other hashers are finished before and after writing a constant byte: they must mix their state when
+    /// finishing for the two hashes to look independent, and hashers with a trivial finalization (e.g. FxHash)
+    /// degrade the false positive rate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`BloomFilter::try_with_hasher`] for a fallible version.
+    pub fn with_hasher(items_count: usize, fp_rate: f64, hash_builder: S) -> Self {
+        params::expect_valid(
+            Self::try_with_hasher(items_count, fp_rate, hash_builder),
+            "bloom filter",
+        )
+    }
+
+    /// Fallible version of [`BloomFilter::with_hasher`], see [`BloomFilter::try_new`] for the possible errors.
+    pub fn try_with_hasher(
+        items_count: usize,
+        fp_rate: f64,
+        hash_builder: S,
+    ) -> Result<Self, BloomError> {
+        // compute the optimal number of bits to use as filter size and of hash functions to use
+        let params = BloomParams::from_items_and_fp_rate(items_count, fp_rate)?;
+        Ok(Self::with_params(params, hash_builder))
+    }
+
+    /// Create a new BloomFilter with exactly `num_bits` bits and `num_hashes` hash functions, hashing items
+    /// with hashers built by `hash_builder`, see [`BloomFilter::with_bits_and_hashes`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`BloomFilter::try_with_bits_hashes_and_hasher`] for a fallible
+    /// version.
+    pub fn with_bits_hashes_and_hasher(num_bits: usize, num_hashes: u32, hash_builder: S) -> Self {
+        params::expect_valid(
+            Self::try_with_bits_hashes_and_hasher(num_bits, num_hashes, hash_builder),
+            "bloom filter",
+        )
+    }
+
+    /// Fallible version of [`BloomFilter::with_bits_hashes_and_hasher`], see
+    /// [`BloomFilter::try_with_bits_and_hashes`] for the possible errors.
+    pub fn try_with_bits_hashes_and_hasher(
+        num_bits: usize,
+        num_hashes: u32,
+        hash_builder: S,
+    ) -> Result<Self, BloomError> {
+        let params = BloomParams::from_bits_and_hashes(num_bits, num_hashes)?;
+        Ok(Self::with_params(params, hash_builder))
+    }
+
+    /// Create a new BloomFilter sized by `params`, hashing items with hashers built by `hash_builder`.
+    pub fn with_params(params: BloomParams, hash_builder: S) -> Self {
+        BloomFilter {
+            bitmap: bitvec![u64, Lsb0; 0; params.num_bits()],
+            optimal_m: params.num_bits() as u64,
+            optimal_k: params.num_hashes(),
+            capacity: params.items_count(),
+            inserted_count: 0,
+            hash_builder,
+            _marker: PhantomData,
+        }
     }
 
     /// Insert an element into the Bloom Filter.
     pub fn insert(&mut self, item: &T) {
         // obtain h1 and h2, the two images of item by our two kernel hashing functions
         let (h1, h2) = self.hash_kernel(item);
+        self.insert_hashes(h1, h2);
+    }
 
-        // for each of our actual k hash functions, derive the index in the bitvec we need to set to 1
-        for k_i in 0..self.optimal_k {
-            let index = self.get_index(h1, h2, k_i as u64);
-            // this won't panic with out of bounds since index is enforced to be smaller than self.optimal_m, the size of the bitvec
-            self.bitmap.set(index, true);
-        }
+    /// Insert an element into the Bloom Filter, returning whether it was possibly already present.
+    ///
+    /// This is equivalent to calling [`BloomFilter::contains`] then [`BloomFilter::insert`], but hashes the item
+    /// only once. It returns `true` if all the bits of the item were already set, which happens either because
+    /// the item was inserted before or because of a false positive.
+    pub fn insert_check(&mut self, item: &T) -> bool {
+        let (h1, h2) = self.hash_kernel(item);
+        self.insert_hashes(h1, h2)
     }
 
     /// Checks if an element is contained in the bloom filter.
     /// If this returns true, either the element is indeed in the filter or it isn't according to the false positive rate the user selected when building the filter
     /// If this returns false, the element is not in the set.
-    pub fn contains(&mut self, item: &T) -> bool {
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
         let (h1, h2) = self.hash_kernel(item);
+        self.contains_hashes(h1, h2)
+    }
+
+    /// Insert the exact bytes of `bytes` into the filter, bypassing the `Hash` implementation of `T`.
+    ///
+    /// The standard `Hash` implementations add framing to the bytes they write, a length prefix for slices and a
+    /// `0xff` terminator for strings, and differ between types, so the bits an item sets are specific to Rust. Keys
+    /// inserted with this method skip this framing and map to bits which only depend on their bytes, which lets
+    /// other implementations (e.g. a Python, Java or Go binding) build and query compatible filters. With the
+    /// default [`SeededState`], the bits of a key are derived as follows:
+    ///
+    /// 1. the SipHash-1-3 keys `k0` and `k1` are the first two outputs of SplitMix64 seeded with the seed of the
+    ///    filter,
+    /// 2. `h1` and `h2` are the first and second 64-bit little endian halves of the 128-bit SipHash-1-3 digest of
+    ///    the key bytes,
+    /// 3. the `i`-th hash function, for `i` in `0..k`, sets the bit `(h1 + i * h2 + (i^3 - i) / 6) mod m`, computed
+    ///    with wrapping 64-bit unsigned arithmetic, where `m` is [`BloomFilter::num_bits`] and `k` is
+    ///    [`BloomFilter::num_hashes`],
+    /// 4. the bit `b` of the filter is the bit `b mod 64` (least significant first) of its `b / 64`-th 64-bit word,
+    ///    which is how bitmaps are laid out by [`BloomFilter::to_bytes`].
+    ///
+    /// Bytes inserted with this method must be looked up with [`BloomFilter::contains_bytes`]: as `[u8]` and `str`
+    /// hash with their framing, `contains(key)` doesn't find them.
+    ///
+    /// Example usage:
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    ///
+    /// let mut bloom = BloomFilter::<[u8]>::with_seed(100, 0.01, 42);
+    /// bloom.insert_bytes(b"key");
+    /// assert!(bloom.contains_bytes(b"key"));
+    /// ```
+    pub fn insert_bytes(&mut self, bytes: &[u8]) {
+        let (h1, h2) = hash::hash_bytes_kernel(&self.hash_builder, bytes);
+        self.insert_hashes(h1, h2);
+    }
 
+    /// Checks if the exact bytes of `bytes` were inserted with [`BloomFilter::insert_bytes`].
+    pub fn contains_bytes(&self, bytes: &[u8]) -> bool {
+        let (h1, h2) = hash::hash_bytes_kernel(&self.hash_builder, bytes);
+        self.contains_hashes(h1, h2)
+    }
+
+    /// Insert an element given its kernel hashes, returning whether all its bits were already set.
+    pub(crate) fn insert_hashes(&mut self, h1: u64, h2: u64) -> bool {
+        let mut present = true;
+        // for each of our actual k hash functions, derive the index in the bitvec we need to set to 1
+        for k_i in 0..self.optimal_k {
+            let index = self.get_index(h1, h2, k_i as u64);
+            // this won't panic with out of bounds since index is enforced to be smaller than self.optimal_m, the size of the bitvec
+            present &= self.bitmap.replace(index, true);
+        }
+        self.inserted_count += 1;
+        present
+    }
+
+    /// Checks if an element is contained in the bloom filter given its kernel hashes.
+    pub(crate) fn contains_hashes(&self, h1: u64, h2: u64) -> bool {
         for k_i in 0..self.optimal_k {
             let index = self.get_index(h1, h2, k_i as u64);
 
-            #[allow(clippy::single_match)]
-            match self.bitmap.get(index) {
-                Some(boolean) => {
-                    if !boolean {
-                        return false;
-                    }
+            if let Some(boolean) = self.bitmap.get(index) {
+                if !boolean {
+                    return false;
                 }
-                None => {}
             }
         }
 
         true
     }
 
-    fn hash_kernel(&self, item: &T) -> (u64, u64) {
-        // we don't want the hashing to influence the state of the hasher for further computations
-        // so we clone our two hashers before hashing.
-        let hasher1 = &mut self.hashers[0].clone();
-        let hasher2 = &mut self.hashers[1].clone();
+    fn hash_kernel<Q: ?Sized + Hash>(&self, item: &Q) -> (u64, u64) {
+        hash::hash_kernel(&self.hash_builder, item)
+    }
+
+    fn get_index(&self, h1: u64, h2: u64, k_i: u64) -> usize {
+        hash::get_index(h1, h2, k_i, self.optimal_m)
+    }
+}
+
+impl<T: ?Sized, S> BloomFilter<T, S> {
+    /// The number of bits of this filter.
+    pub fn num_bits(&self) -> usize {
+        self.optimal_m as usize
+    }
 
-        item.hash(hasher1);
-        item.hash(hasher2);
+    /// The number of hash functions of this filter.
+    pub fn num_hashes(&self) -> u32 {
+        self.optimal_k
+    }
 
-        let hash1 = hasher1.finish();
-        let hash2 = hasher2.finish();
+    /// The number of items this filter was sized for, past which its false positive rate exceeds the
+    /// target it was built with.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
 
-        (hash1, hash2)
+    /// The number of insertions performed on this filter, including repeated insertions of the same item.
+    pub fn inserted_count(&self) -> u64 {
+        self.inserted_count
     }
 
-    fn get_index(&self, h1: u64, h2: u64, k_i: u64) -> usize {
-        // compute H_k(x) = h1(x) + k_i * h2(x) and use it to index into the m elements of the bitvec
-        (h1.wrapping_add((k_i).wrapping_mul(h2)) % self.optimal_m) as usize
+    /// The hash builder used by this filter.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
     }
 }
 
@@ -135,4 +374,290 @@ mod tests {
         bloom.insert("item_1");
         assert!(bloom.contains("item_1"));
     }
+
+    #[test]
+    fn insert_check() {
+        let mut bloom = BloomFilter::with_seed(100, 0.01, 0);
+        assert!(!bloom.insert_check("item_1"));
+        assert!(bloom.insert_check("item_1"));
+        assert!(bloom.contains("item_1"));
+        assert!(!bloom.insert_check("item_2"));
+        assert_eq!(bloom.inserted_count(), 3);
+
+        let mut expected = BloomFilter::with_seed(100, 0.01, 0);
+        expected.insert("item_1");
+        expected.insert("item_2");
+        assert_eq!(bloom.bitmap, expected.bitmap);
+    }
+
+    #[test]
+    fn borrowed_lookups() {
+        let mut bloom = BloomFilter::with_seed(100, 0.01, 0);
+        bloom.insert(&"item_1".to_string());
+        bloom.insert(&"item_2".to_string());
+        assert_eq!(
+            bloom.hash_kernel(&"item".to_string()),
+            bloom.hash_kernel("item")
+        );
+        assert!(bloom.contains("item_1"));
+        assert!(bloom.contains(&"item_2".to_string()));
+        assert!(!bloom.contains("item_3"));
+    }
+
+    #[test]
+    fn with_bits_and_hashes() {
+        let mut bloom = BloomFilter::with_bits_and_hashes(1000, 5);
+        assert_eq!(bloom.num_bits(), 1000);
+        assert_eq!(bloom.num_hashes(), 5);
+        assert_eq!(bloom.capacity(), 138);
+        bloom.insert("item");
+        assert!(bloom.contains("item"));
+
+        let bloom = BloomFilter::<str>::new(100, 0.01);
+        assert_eq!(bloom.num_bits(), 960);
+        assert_eq!(bloom.num_hashes(), 7);
+        assert_eq!(bloom.capacity(), 100);
+    }
+
+    #[test]
+    fn try_with_bits_and_hashes_rejects_invalid_parameters() {
+        assert_eq!(
+            BloomFilter::<str>::try_with_bits_and_hashes(0, 5).err(),
+            Some(BloomError::ZeroBitsCount)
+        );
+        assert_eq!(
+            BloomFilter::<str>::try_with_bits_and_hashes(1000, 0).err(),
+            Some(BloomError::ZeroHashesCount)
+        );
+        assert_eq!(
+            BloomFilter::<str>::try_with_bits_and_hashes(usize::MAX, 5).err(),
+            Some(BloomError::BitmapSizeOverflow)
+        );
+        assert_eq!(
+            BloomFilter::<str>::try_with_bits_hashes_and_hasher(0, 5, SeededState::with_seed(7))
+                .err(),
+            Some(BloomError::ZeroBitsCount)
+        );
+    }
+
+    #[test]
+    fn bits_hashes_and_seed() {
+        let mut bloom_1 =
+            BloomFilter::with_bits_hashes_and_hasher(1000, 5, SeededState::with_seed(7));
+        let mut bloom_2 =
+            BloomFilter::with_bits_hashes_and_hasher(1000, 5, SeededState::with_seed(7));
+        for bloom in [&mut bloom_1, &mut bloom_2] {
+            bloom.insert("item");
+        }
+        assert_eq!(bloom_1.num_bits(), 1000);
+        assert_eq!(bloom_1.num_hashes(), 5);
+        assert_eq!(bloom_1.seed(), 7);
+        assert_eq!(bloom_1.bitmap, bloom_2.bitmap);
+    }
+
+    #[test]
+    fn same_seed_same_bits() {
+        let mut bloom_1 = BloomFilter::with_seed(100, 0.01, 7);
+        let mut bloom_2 = BloomFilter::with_seed(100, 0.01, 7);
+        let mut bloom_3 = BloomFilter::with_seed(100, 0.01, 8);
+        for bloom in [&mut bloom_1, &mut bloom_2, &mut bloom_3] {
+            bloom.insert("item");
+        }
+        assert_eq!(bloom_1.bitmap, bloom_2.bitmap);
+        assert_ne!(bloom_1.bitmap, bloom_3.bitmap);
+    }
+
+    #[test]
+    fn golden_indexes() {
+        fn indexes<T: ?Sized + Hash, S: BuildHasher + 'static>(
+            bloom: &BloomFilter<T, S>,
+            item: &T,
+        ) -> Vec<usize> {
+            let (h1, h2) = bloom.hash_kernel(item);
+            (0..bloom.optimal_k as u64)
+                .map(|k_i| bloom.get_index(h1, h2, k_i))
+                .collect()
+        }
+
+        let bloom = BloomFilter::<str>::with_seed(100, 0.01, 42);
+        assert_eq!(
+            indexes(&bloom, "item"),
+            vec![169, 944, 760, 578, 399, 224, 54]
+        );
+        assert_eq!(indexes(&bloom, ""), vec![239, 398, 558, 464, 629, 798, 716]);
+
+        let bloom = BloomFilter::<[u8]>::with_seed(1000, 0.001, 0);
+        assert_eq!(
+            indexes(&bloom, b"bloom"),
+            vec![13419, 4421, 9824, 829, 6237, 11649, 6250, 11673, 2703, 8141]
+        );
+    }
+
+    #[test]
+    fn golden_byte_indexes() {
+        // computed by an independent implementation of the scheme documented on `insert_bytes`
+        for (key, mut expected) in [
+            (&b"key"[..], vec![182, 489, 535, 648, 797, 837, 941]),
+            (&b""[..], vec![18, 309, 486, 608, 770, 810, 959]),
+        ] {
+            let mut bloom = BloomFilter::<[u8]>::with_seed(100, 0.01, 42);
+            bloom.insert_bytes(key);
+            assert!(bloom.contains_bytes(key));
+            expected.sort();
+            assert_eq!(bloom.bitmap.iter_ones().collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn bytes_bypass_hash() {
+        let mut bloom = BloomFilter::<[u8]>::with_seed(100, 0.01, 0);
+        assert_ne!(
+            bloom.hash_kernel(&b"key"[..]),
+            hash::hash_bytes_kernel(&bloom.hash_builder, b"key")
+        );
+        bloom.insert_bytes(b"key");
+        assert!(bloom.contains_bytes(b"key"));
+        assert!(!bloom.contains(&b"key"[..]));
+        assert!(!bloom.contains_bytes(b"other_key"));
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        for fp_rate in [0.1, 0.01, 0.001] {
+            let mut bloom = BloomFilter::with_seed(10_000, fp_rate, 0);
+            for i in 0..10_000u32 {
+                bloom.insert(&i);
+            }
+            let false_positives = (10_000..110_000u32).filter(|i| bloom.contains(i)).count();
+            let measured = false_positives as f64 / 100_000.0;
+            assert!(
+                measured < fp_rate * 1.2,
+                "measured fp rate {measured} for target {fp_rate}"
+            );
+        }
+    }
+
+    #[test]
+    fn small_filters_false_positive_rate() {
+        for items_count in [1, 2, 5] {
+            let mut false_positives = 0;
+            for seed in 0..100 {
+                let mut bloom = BloomFilter::with_seed(items_count, 0.01, seed);
+                for i in 0..items_count as u32 {
+                    bloom.insert(&i);
+                }
+                false_positives += (1000..3000u32).filter(|i| bloom.contains(i)).count();
+            }
+            let measured = false_positives as f64 / 200_000.0;
+            assert!(
+                measured < 0.01,
+                "measured fp rate {measured} for {items_count} items"
+            );
+        }
+    }
+
+    #[test]
+    fn fp_rate_compared_to_two_pass_kernel() {
+        // the kernel used to hash each item twice, the second time after a constant byte
+        use std::hash::Hasher;
+        fn two_pass_kernel(hash_builder: &SeededState, item: &u32) -> (u64, u64) {
+            let mut hasher2 = hash_builder.build_hasher();
+            hasher2.write_u8(1);
+            item.hash(&mut hasher2);
+            (hash_builder.hash_one(item), hasher2.finish())
+        }
+
+        for fp_rate in [0.1, 0.01, 0.001] {
+            let mut single_pass = BloomFilter::with_seed(10_000, fp_rate, 0);
+            let mut two_pass = BloomFilter::<u32>::with_seed(10_000, fp_rate, 0);
+            for i in 0..10_000u32 {
+                single_pass.insert(&i);
+                let (h1, h2) = two_pass_kernel(&two_pass.hash_builder, &i);
+                two_pass.insert_hashes(h1, h2);
+            }
+            let single_pass_fp = (10_000..210_000u32)
+                .filter(|i| single_pass.contains(i))
+                .count() as f64
+                / 200_000.0;
+            let two_pass_fp = (10_000..210_000u32)
+                .filter(|i| {
+                    let (h1, h2) = two_pass_kernel(&two_pass.hash_builder, i);
+                    two_pass.contains_hashes(h1, h2)
+                })
+                .count() as f64
+                / 200_000.0;
+            assert!(
+                (single_pass_fp - two_pass_fp).abs() < fp_rate * 0.3,
+                "single pass fp rate {single_pass_fp}, two pass {two_pass_fp} for target {fp_rate}"
+            );
+        }
+    }
+
+    #[test]
+    fn with_custom_hasher() {
+        let mut bloom = BloomFilter::with_hasher(
+            100,
+            0.01,
+            std::hash::BuildHasherDefault::<std::collections::hash_map::DefaultHasher>::default(),
+        );
+        bloom.insert("item");
+        assert!(bloom.contains("item"));
+        assert!(!bloom.contains("other_item"));
+    }
+
+    #[test]
+    fn is_send_and_sync() {
+        fn assert_send_sync<B: Send + Sync>() {}
+        assert_send_sync::<BloomFilter<str>>();
+        // holds even for item types which are neither Send nor Sync
+        assert_send_sync::<BloomFilter<std::rc::Rc<u8>>>();
+    }
+
+    #[test]
+    fn concurrent_contains() {
+        let mut bloom = BloomFilter::new(1000, 0.01);
+        for i in 0..1000u32 {
+            bloom.insert(&i);
+        }
+        let bloom = std::sync::Arc::new(bloom);
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let bloom = std::sync::Arc::clone(&bloom);
+                std::thread::spawn(move || (0..1000u32).all(|i| bloom.contains(&i)))
+            })
+            .collect();
+
+        for handle in handles {
+            assert!(handle.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn try_new_rejects_invalid_parameters() {
+        assert_eq!(
+            BloomFilter::<str>::try_new(0, 0.01).err(),
+            Some(BloomError::ZeroItemsCount)
+        );
+        for fp_rate in [0.0, -0.5, 1.0, 1.5, f64::INFINITY] {
+            assert_eq!(
+                BloomFilter::<str>::try_new(100, fp_rate).err(),
+                Some(BloomError::InvalidFpRate(fp_rate))
+            );
+        }
+        assert!(matches!(
+            BloomFilter::<str>::try_new(100, f64::NAN),
+            Err(BloomError::InvalidFpRate(fp_rate)) if fp_rate.is_nan()
+        ));
+        assert_eq!(
+            BloomFilter::<str>::try_new(usize::MAX, 1e-300).err(),
+            Some(BloomError::BitmapSizeOverflow)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid bloom filter parameters")]
+    fn new_panics_on_invalid_parameters() {
+        BloomFilter::<str>::new(100, 0.0);
+    }
 }
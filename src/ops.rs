@@ -0,0 +1,199 @@
+use std::{
+    marker::PhantomData,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign},
+};
+
+use crate::{BloomError, BloomFilter};
+
+impl<T: ?Sized, S: Clone> Clone for BloomFilter<T, S> {
+    fn clone(&self) -> Self {
+        BloomFilter {
+            bitmap: self.bitmap.clone(),
+            optimal_m: self.optimal_m,
+            optimal_k: self.optimal_k,
+            capacity: self.capacity,
+            inserted_count: self.inserted_count,
+            hash_builder: self.hash_builder.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized, S: PartialEq> BloomFilter<T, S> {
+    /// Checks if two filters can be combined, i.e. if they have the same number of bits, of hash functions,
+    /// and the same hashers (for instance the same seed), so that an item sets the same bits in both.
+    pub fn is_compatible(&self, other: &Self) -> bool {
+        self.optimal_m == other.optimal_m
+            && self.optimal_k == other.optimal_k
+            && self.hash_builder == other.hash_builder
+    }
+
+    /// Merge `other` into this filter, which then contains every item of both filters.
+    ///
+    /// The inserted count of the union is the sum of both inserted counts. Returns an error and leaves this
+    /// filter untouched if the filters are not compatible, see [`BloomFilter::is_compatible`].
+    pub fn union_inplace(&mut self, other: &Self) -> Result<(), BloomError> {
+        if !self.is_compatible(other) {
+            return Err(BloomError::IncompatibleFilters);
+        }
+        self.bitmap |= &other.bitmap;
+        self.inserted_count = self.inserted_count.saturating_add(other.inserted_count);
+        Ok(())
+    }
+
+    /// Intersect this filter with `other`, it then only contains the items present in both filters.
+    ///
+    /// The intersection may report more false positives than a filter built from the common items only.
+    /// Its inserted count is the smallest of both inserted counts. Returns an error and leaves this filter
+    /// untouched if the filters are not compatible, see [`BloomFilter::is_compatible`].
+    pub fn intersect_inplace(&mut self, other: &Self) -> Result<(), BloomError> {
+        if !self.is_compatible(other) {
+            return Err(BloomError::IncompatibleFilters);
+        }
+        self.bitmap &= &other.bitmap;
+        self.inserted_count = self.inserted_count.min(other.inserted_count);
+        Ok(())
+    }
+}
+
+/// Union of two filters.
+///
+/// # Panics
+///
+/// Panics if the filters are not compatible, see [`BloomFilter::union_inplace`] for a fallible version.
+impl<T: ?Sized, S: PartialEq + Clone> BitOr for &BloomFilter<T, S> {
+    type Output = BloomFilter<T, S>;
+
+    fn bitor(self, rhs: Self) -> BloomFilter<T, S> {
+        let mut union = self.clone();
+        union |= rhs;
+        union
+    }
+}
+
+/// Intersection of two filters.
+///
+/// # Panics
+///
+/// Panics if the filters are not compatible, see [`BloomFilter::intersect_inplace`] for a fallible version.
+impl<T: ?Sized, S: PartialEq + Clone> BitAnd for &BloomFilter<T, S> {
+    type Output = BloomFilter<T, S>;
+
+    fn bitand(self, rhs: Self) -> BloomFilter<T, S> {
+        let mut intersection = self.clone();
+        intersection &= rhs;
+        intersection
+    }
+}
+
+/// In place union of two filters.
+///
+/// # Panics
+///
+/// Panics if the filters are not compatible, see [`BloomFilter::union_inplace`] for a fallible version.
+impl<T: ?Sized, S: PartialEq> BitOrAssign<&BloomFilter<T, S>> for BloomFilter<T, S> {
+    fn bitor_assign(&mut self, rhs: &BloomFilter<T, S>) {
+        if let Err(err) = self.union_inplace(rhs) {
+            panic!("cannot compute the union of bloom filters: {err}");
+        }
+    }
+}
+
+/// In place intersection of two filters.
+///
+/// # Panics
+///
+/// Panics if the filters are not compatible, see [`BloomFilter::intersect_inplace`] for a fallible version.
+impl<T: ?Sized, S: PartialEq> BitAndAssign<&BloomFilter<T, S>> for BloomFilter<T, S> {
+    fn bitand_assign(&mut self, rhs: &BloomFilter<T, S>) {
+        if let Err(err) = self.intersect_inplace(rhs) {
+            panic!("cannot compute the intersection of bloom filters: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters() -> (BloomFilter<u32>, BloomFilter<u32>) {
+        let mut bloom_1 = BloomFilter::with_seed(1000, 0.01, 0);
+        let mut bloom_2 = BloomFilter::with_seed(1000, 0.01, 0);
+        for i in 0..1000 {
+            bloom_1.insert(&i);
+            bloom_2.insert(&(i + 500));
+        }
+        (bloom_1, bloom_2)
+    }
+
+    #[test]
+    fn union() {
+        let (bloom_1, bloom_2) = filters();
+        let union = &bloom_1 | &bloom_2;
+        assert_eq!(union.inserted_count(), 2000);
+        for i in 0..10_000 {
+            if bloom_1.contains(&i) || bloom_2.contains(&i) {
+                assert!(union.contains(&i));
+            }
+        }
+
+        // the union is exactly the filter of the union of both sets of items
+        let mut expected = BloomFilter::with_seed(1000, 0.01, 0);
+        for i in 0..1500 {
+            expected.insert(&i);
+        }
+        assert_eq!(union.bitmap, expected.bitmap);
+
+        let mut union_inplace = bloom_1.clone();
+        union_inplace.union_inplace(&bloom_2).unwrap();
+        assert_eq!(union_inplace.bitmap, union.bitmap);
+    }
+
+    #[test]
+    fn intersection() {
+        let (bloom_1, bloom_2) = filters();
+        let intersection = &bloom_1 & &bloom_2;
+        assert_eq!(intersection.inserted_count(), 1000);
+        for i in 500..1000 {
+            assert!(intersection.contains(&i));
+        }
+        for i in 0..10_000 {
+            if intersection.contains(&i) {
+                assert!(bloom_1.contains(&i) && bloom_2.contains(&i));
+            }
+        }
+
+        let mut intersect_inplace = bloom_1.clone();
+        intersect_inplace.intersect_inplace(&bloom_2).unwrap();
+        assert_eq!(intersect_inplace.bitmap, intersection.bitmap);
+    }
+
+    #[test]
+    fn incompatible_filters() {
+        let (mut bloom, _) = filters();
+        let before = bloom.bitmap.clone();
+        let other_seed = BloomFilter::with_seed(1000, 0.01, 1);
+        let other_size = BloomFilter::with_seed(2000, 0.01, 0);
+        let other_hashes = BloomFilter::with_bits_and_hashes(bloom.num_bits(), 3);
+
+        for other in [other_seed, other_size, other_hashes] {
+            assert!(!bloom.is_compatible(&other));
+            assert_eq!(
+                bloom.union_inplace(&other),
+                Err(BloomError::IncompatibleFilters)
+            );
+            assert_eq!(
+                bloom.intersect_inplace(&other),
+                Err(BloomError::IncompatibleFilters)
+            );
+        }
+        assert_eq!(bloom.bitmap, before);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot compute the union")]
+    fn union_operator_panics_on_incompatible_filters() {
+        let _ = &BloomFilter::<u32>::with_seed(1000, 0.01, 0)
+            | &BloomFilter::<u32>::with_seed(1000, 0.01, 1);
+    }
+}
@@ -0,0 +1,171 @@
+use std::{
+    hash::{BuildHasher, Hash},
+    mem,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use bitvec::prelude::*;
+use rayon::prelude::*;
+
+use crate::{hash, params, BloomFilter, BloomParams, SeededState};
+
+impl<T: ?Sized + Hash + Sync> BloomFilter<T> {
+    /// Create a new BloomFilter sized for the items of `iter` at the expected false positive rate, hashing items
+    /// with a random seed, and insert them all in parallel on the rayon thread pool.
+    ///
+    /// This is the parallel version of [`BloomFilter::from_iter_with_fp`]. To get a filter bit-identical to a
+    /// sequential build, create it with [`BloomFilter::with_seed`] and fill it with [`BloomFilter::par_extend`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fp_rate` is not in the open interval `(0, 1)` or if the filter would be too large to be allocated.
+    ///
+    /// Example usage:
+    /// ```
+    /// use bloom_filter::BloomFilter;
+    ///
+    /// let items: Vec<u64> = (0..10_000).collect();
+    /// let bloom = BloomFilter::par_from_iter(&items, 0.01);
+    /// assert!(bloom.contains_all(&items));
+    /// ```
+    pub fn par_from_iter<'a, I>(iter: I, fp_rate: f64) -> Self
+    where
+        T: 'a,
+        I: IntoParallelIterator<Item = &'a T>,
+        I::Iter: IndexedParallelIterator,
+    {
+        let iter = iter.into_par_iter();
+        let mut bloom = params::expect_valid(
+            BloomParams::from_items_and_fp_rate(iter.len().max(1), fp_rate)
+                .map(|params| Self::with_params(params, SeededState::new())),
+            "bloom filter",
+        );
+        bloom.par_extend(iter);
+        bloom
+    }
+}
+
+impl<T: ?Sized + Hash + Sync, S: BuildHasher + Sync + 'static> BloomFilter<T, S> {
+    /// Insert all the elements of `iter` in parallel on the rayon thread pool.
+    ///
+    /// Threads set bits directly in the shared bitmap with atomic `fetch_or`s rather than filling partial bitmaps,
+    /// so this needs no extra memory whatever the size of the filter. Setting bits commutes, so the filter ends up
+    /// bit-identical to one filled sequentially with the same items.
+    ///
+    /// If hashing an item panics, the panic is propagated once the other threads are done. The filter stays
+    /// usable: it still contains the items inserted before, along with an unspecified subset of the bits of
+    /// `iter`, which are not counted by [`BloomFilter::inserted_count`].
+    pub fn par_extend<'a, I>(&mut self, iter: I)
+    where
+        T: 'a,
+        I: IntoParallelIterator<Item = &'a T>,
+    {
+        let words = AtomicWords::take(&mut self.bitmap);
+        let (hash_builder, optimal_k, optimal_m) =
+            (&self.hash_builder, self.optimal_k, self.optimal_m);
+
+        let inserted = iter
+            .into_par_iter()
+            .map(|item| {
+                let (h1, h2) = hash::hash_kernel(hash_builder, item);
+                for k_i in 0..optimal_k as u64 {
+                    let index = hash::get_index(h1, h2, k_i, optimal_m);
+                    words.words[index / 64].fetch_or(1 << (index % 64), Ordering::Relaxed);
+                }
+            })
+            .count();
+
+        // rayon joins all its tasks before returning, so every bit set by them is visible when the words are
+        // moved back into the bitmap
+        drop(words);
+        self.inserted_count += inserted as u64;
+    }
+}
+
+/// The words of a bitmap temporarily moved out of it to be updated atomically.
+///
+/// They are moved back into the bitmap when this is dropped, including while unwinding from a panic, so that the
+/// filter is never left with an empty bitmap.
+struct AtomicWords<'a> {
+    bitmap: &'a mut BitVec<u64, Lsb0>,
+    words: Vec<AtomicU64>,
+    len: usize,
+}
+
+impl<'a> AtomicWords<'a> {
+    fn take(bitmap: &'a mut BitVec<u64, Lsb0>) -> Self {
+        let len = bitmap.len();
+        let words = mem::take(bitmap)
+            .into_vec()
+            .into_iter()
+            .map(AtomicU64::new)
+            .collect();
+        AtomicWords { bitmap, words, len }
+    }
+}
+
+impl Drop for AtomicWords<'_> {
+    fn drop(&mut self) {
+        let words = mem::take(&mut self.words);
+        let mut bitmap = BitVec::from_vec(words.into_iter().map(AtomicU64::into_inner).collect());
+        bitmap.truncate(self.len);
+        *self.bitmap = bitmap;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn par_extend_matches_sequential_build() {
+        let items: Vec<u64> = (0..100_000).collect();
+        let mut parallel = BloomFilter::with_seed(100_000, 0.01, 5);
+        let mut sequential = BloomFilter::with_seed(100_000, 0.01, 5);
+        parallel.insert(&u64::MAX);
+        sequential.insert(&u64::MAX);
+
+        parallel.par_extend(&items);
+        sequential.extend(&items);
+        assert_eq!(parallel.bitmap, sequential.bitmap);
+        assert_eq!(parallel.inserted_count(), 100_001);
+    }
+
+    #[test]
+    fn par_extend_panic_keeps_the_bitmap() {
+        struct Item(u64);
+
+        impl Hash for Item {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                assert_ne!(self.0, 5000, "unhashable item");
+                self.0.hash(state);
+            }
+        }
+
+        let mut bloom = BloomFilter::with_seed(10_000, 0.01, 0);
+        bloom.insert(&Item(u64::MAX));
+        let items: Vec<Item> = (0..10_000).map(Item).collect();
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| bloom.par_extend(&items)));
+        assert!(result.is_err());
+
+        assert_eq!(bloom.num_bits(), bloom.bitmap.len());
+        assert!(bloom.contains(&Item(u64::MAX)));
+        assert_eq!(bloom.inserted_count(), 1);
+        bloom.insert(&Item(1));
+        assert!(bloom.contains(&Item(1)));
+    }
+
+    #[test]
+    fn par_from_iter() {
+        let items: Vec<String> = (0..10_000).map(|i| format!("item_{i}")).collect();
+        let bloom = BloomFilter::<str>::par_from_iter(items.par_iter().map(String::as_str), 0.01);
+        assert_eq!(bloom.capacity(), 10_000);
+        assert_eq!(bloom.inserted_count(), 10_000);
+        assert!(items.iter().all(|item| bloom.contains(item)));
+
+        let mut sequential = BloomFilter::with_hasher(10_000, 0.01, *bloom.hasher());
+        sequential.extend(items.iter().map(String::as_str));
+        assert_eq!(bloom.bitmap, sequential.bitmap);
+    }
+}
@@ -0,0 +1,519 @@
+use bitvec::prelude::*;
+
+use crate::BloomError;
+
+/// Number of bits in the words backing the bitmap of a filter, derived sizes are rounded up to it.
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// The sizing parameters of a bloom filter.
+///
+/// A bloom filter is described by four quantities: the number of items `n` it is designed to hold, its number
+/// of bits `m`, its number of hash functions `k` and its false positive rate `p` once it holds `n` items, with
+/// `p = (1 - e^(-k * n / m))^k`. Given any two of them (except `k` and `p`, which only constrain the ratio `m / n`),
+/// the calculator derives the other two:
+/// - `k` is the integer number of hash functions minimizing `p` for the given `m / n` (close to `m / n * ln(2)`),
+/// - a derived `m` is the smallest one for which `p` is met, rounded up to a whole number of 64-bit words,
+/// - a derived `n` is the largest one for which `p` is met,
+/// - `p` is always the exact false positive rate of the final `(n, m, k)`, which is never above a requested rate.
+///
+/// Example usage:
+/// ```
+/// use bloom_filter::BloomParams;
+///
+/// let params = BloomParams::from_items_and_fp_rate(1_000_000, 0.01).unwrap();
+/// assert_eq!(params.num_hashes(), 7);
+/// assert!(params.fp_rate() <= 0.01);
+///
+/// // how many items fit in 1 MiB at the same false positive rate ?
+/// let params = BloomParams::from_memory_budget(1 << 20, 0.01).unwrap();
+/// assert_eq!(params.num_bits(), 8 << 20);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BloomParams {
+    items_count: usize,
+    num_bits: usize,
+    num_hashes: u32,
+    fp_rate: f64,
+}
+
+impl BloomParams {
+    /// Size a filter holding `items_count` items with a false positive rate of at most `fp_rate`.
+    pub fn from_items_and_fp_rate(items_count: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        check_items_count(items_count)?;
+        check_fp_rate(fp_rate)?;
+
+        // for a given k, the smallest m meeting the false positive rate is -k * n / ln(1 - p^(1/k)),
+        // keep whichever of the two integers around the optimal k = -log2(p) needs the fewest bits.
+        let num_bits = hashes_candidates(fp_rate)
+            .map(|k| -(k as f64) * items_count as f64 / (-fp_rate.powf(1.0 / k as f64)).ln_1p())
+            .min_by(|a, b| a.total_cmp(b))
+            .expect("there is always at least one candidate");
+        // rounding up to whole words can add many bits per item to small filters, so pick k for the final m
+        let num_bits = round_to_words(num_bits)?;
+
+        Ok(Self::from_parts(
+            items_count,
+            num_bits,
+            optimal_hashes(items_count, num_bits),
+        ))
+    }
+
+    /// Size a filter holding `items_count` items in exactly `num_bits` bits.
+    pub fn from_items_and_bits(items_count: usize, num_bits: usize) -> Result<Self, BloomError> {
+        check_items_count(items_count)?;
+        check_num_bits(num_bits)?;
+
+        Ok(Self::from_parts(
+            items_count,
+            num_bits,
+            optimal_hashes(items_count, num_bits),
+        ))
+    }
+
+    /// Size a filter holding `items_count` items with `num_hashes` hash functions.
+    pub fn from_items_and_hashes(items_count: usize, num_hashes: u32) -> Result<Self, BloomError> {
+        check_items_count(items_count)?;
+        check_num_hashes(num_hashes)?;
+
+        // k = m / n * ln(2) is optimal, so m = k * n / ln(2)
+        let num_bits =
+            round_to_words(num_hashes as f64 * items_count as f64 / core::f64::consts::LN_2)?;
+
+        Ok(Self::from_parts(items_count, num_bits, num_hashes))
+    }
+
+    /// Size a filter of exactly `num_bits` bits, holding as many items as possible with a false positive rate
+    /// of at most `fp_rate`.
+    pub fn from_bits_and_fp_rate(num_bits: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        check_num_bits(num_bits)?;
+        check_fp_rate(fp_rate)?;
+
+        // for a given k, the largest n meeting the false positive rate is -m * ln(1 - p^(1/k)) / k,
+        // keep whichever of the two integers around the optimal k = -log2(p) holds the most items.
+        let (num_hashes, items_count) = hashes_candidates(fp_rate)
+            .map(|k| {
+                let items = -(num_bits as f64) * (-fp_rate.powf(1.0 / k as f64)).ln_1p() / k as f64;
+                (k, items.floor())
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .expect("there is always at least one candidate");
+        if items_count < 1.0 {
+            return Err(BloomError::CapacityTooSmall);
+        }
+
+        Ok(Self::from_parts(items_count as usize, num_bits, num_hashes))
+    }
+
+    /// Size a filter of exactly `num_bits` bits using `num_hashes` hash functions, holding the number of items
+    /// for which `num_hashes` is optimal.
+    ///
+    /// The resulting number of items is zero if `num_bits` is too small for `num_hashes` to ever be optimal.
+    pub fn from_bits_and_hashes(num_bits: usize, num_hashes: u32) -> Result<Self, BloomError> {
+        check_num_bits(num_bits)?;
+        check_num_hashes(num_hashes)?;
+
+        // k = m / n * ln(2) is optimal, so n = m * ln(2) / k
+        let items_count = (num_bits as f64 * core::f64::consts::LN_2 / num_hashes as f64).floor();
+
+        Ok(Self::from_parts(items_count as usize, num_bits, num_hashes))
+    }
+
+    /// Size a filter fitting in `bytes` bytes, holding as many items as possible with a false positive rate of
+    /// at most `fp_rate`.
+    ///
+    /// The budget is rounded down to a whole number of 64-bit words.
+    pub fn from_memory_budget(bytes: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        Self::from_bits_and_fp_rate(budget_to_bits(bytes)?, fp_rate)
+    }
+
+    /// Size a filter fitting in `bytes` bytes and holding `items_count` items.
+    ///
+    /// The budget is rounded down to a whole number of 64-bit words.
+    pub fn from_items_and_memory_budget(
+        items_count: usize,
+        bytes: usize,
+    ) -> Result<Self, BloomError> {
+        Self::from_items_and_bits(items_count, budget_to_bits(bytes)?)
+    }
+
+    /// Size a blocked filter, which sets all the bits of an item in a single block of `block_bits` bits, holding
+    /// `items_count` items with a false positive rate of at most `fp_rate` and at most `max_hashes` hash functions.
+    ///
+    /// The derived `m` is a whole number of blocks, and `p` is the false positive rate of the blocked filter, see
+    /// [`blocked_false_positive_rate`].
+    pub(crate) fn from_items_and_fp_rate_blocked(
+        items_count: usize,
+        fp_rate: f64,
+        block_bits: usize,
+        max_hashes: u32,
+    ) -> Result<Self, BloomError> {
+        // a blocked filter never needs fewer bits than a standard one
+        let standard = Self::from_items_and_fp_rate(items_count, fp_rate)?;
+        let max_hashes = hashes_candidates(fp_rate)
+            .max()
+            .expect("there is always at least one candidate")
+            .min(max_hashes);
+        let best = |num_blocks: usize| {
+            let num_bits = num_blocks * block_bits;
+            (1..=max_hashes)
+                .map(|k| {
+                    let p = blocked_false_positive_rate(items_count, num_bits, k, block_bits);
+                    (k, p)
+                })
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .expect("there is always at least one candidate")
+        };
+        let max_blocks = BitSlice::<u64, Lsb0>::MAX_BITS / block_bits;
+
+        // the false positive rate decreases with the number of blocks: grow it geometrically until the rate is
+        // met, then bisect between the last two sizes
+        let (mut low, mut high) = (0, standard.num_bits().div_ceil(block_bits));
+        while best(high).1 > fp_rate {
+            if high == max_blocks {
+                return Err(BloomError::BitmapSizeOverflow);
+            }
+            (low, high) = (high, (high + high / 8 + 1).min(max_blocks));
+        }
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if best(mid).1 > fp_rate {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+
+        let (num_hashes, fp_rate) = best(high);
+        Ok(BloomParams {
+            items_count,
+            num_bits: high * block_bits,
+            num_hashes,
+            fp_rate,
+        })
+    }
+
+    /// The number of items the filter is designed to hold.
+    pub fn items_count(&self) -> usize {
+        self.items_count
+    }
+
+    /// The number of bits of the filter.
+    pub fn num_bits(&self) -> usize {
+        self.num_bits
+    }
+
+    /// The number of hash functions of the filter.
+    pub fn num_hashes(&self) -> u32 {
+        self.num_hashes
+    }
+
+    /// The false positive rate of the filter once it holds [`BloomParams::items_count`] items.
+    pub fn fp_rate(&self) -> f64 {
+        self.fp_rate
+    }
+
+    fn from_parts(items_count: usize, num_bits: usize, num_hashes: u32) -> Self {
+        BloomParams {
+            items_count,
+            num_bits,
+            num_hashes,
+            fp_rate: false_positive_rate(items_count, num_bits, num_hashes),
+        }
+    }
+}
+
+/// The false positive rate of a filter of `num_bits` bits and `num_hashes` hash functions holding `items_count` items.
+pub(crate) fn false_positive_rate(items_count: usize, num_bits: usize, num_hashes: u32) -> f64 {
+    let k = num_hashes as f64;
+    (-(-k * items_count as f64 / num_bits as f64).exp_m1()).powf(k)
+}
+
+/// The false positive rate of a blocked filter of `num_bits` bits split into blocks of `block_bits` bits, with
+/// `num_hashes` hash functions and holding `items_count` items.
+///
+/// Following Putze, Sanders and Singler, "Cache-, Hash- and Space-Efficient Bloom Filters" (2007), the number of
+/// items in a block follows a Poisson distribution, and a block holding `i` items is a standard filter of
+/// `block_bits` bits. Blocks holding more items than average have a much higher false positive rate, which is why
+/// blocked filters need more bits per item than standard ones, especially with small blocks and low rates.
+///
+/// The false positive rate of a block is `E[(X / B)^k]`, with `X` the number of bits set among its `B` bits. The
+/// usual `(E[X] / B)^k` underestimates it for blocks as small as a word, so it is corrected with the variance of
+/// `X` at the second order.
+pub(crate) fn blocked_false_positive_rate(
+    items_count: usize,
+    num_bits: usize,
+    num_hashes: u32,
+    block_bits: usize,
+) -> f64 {
+    let lambda = items_count as f64 * block_bits as f64 / num_bits as f64;
+    let (k, b) = (num_hashes as f64, block_bits as f64);
+    let block_fp_rate = |i: u64| {
+        if i == 0 {
+            return 0.0;
+        }
+        // each of the i * k bits set in the block leaves a given bit unset with probability 1 - 1 / B
+        let bits = k * i as f64;
+        let unset = (bits * (-1.0 / b).ln_1p()).exp();
+        let two_unset = (bits * (-2.0 / b).ln_1p()).exp();
+        let mean = b * (1.0 - unset);
+        let variance = (b * (b - 1.0) * two_unset + b * unset - b * b * unset * unset).max(0.0);
+        ((mean / b).powf(k) * (1.0 + k * (k - 1.0) * variance / (2.0 * mean * mean))).min(1.0)
+    };
+
+    // sum the Poisson probabilities outwards from the mode, relative to the probability of the mode and
+    // normalized at the end so that none of them underflows for large means
+    let spread = 12.0 * lambda.sqrt() + 12.0;
+    let (mode, high) = (lambda.floor() as u64, (lambda + spread) as u64);
+    let low = (lambda - spread).max(0.0) as u64;
+    let (mut total, mut fp_rate) = (0.0, 0.0);
+    let mut weight = 1.0;
+    for i in mode..=high {
+        total += weight;
+        fp_rate += weight * block_fp_rate(i);
+        weight *= lambda / (i + 1) as f64;
+    }
+    let mut weight = mode as f64 / lambda;
+    for i in (low..mode).rev() {
+        total += weight;
+        fp_rate += weight * block_fp_rate(i);
+        weight *= i as f64 / lambda;
+    }
+    fp_rate / total
+}
+
+/// The integer number of hash functions minimizing the false positive rate for a given ratio of bits per item.
+fn optimal_hashes(items_count: usize, num_bits: usize) -> u32 {
+    let k = num_bits as f64 / items_count as f64 * core::f64::consts::LN_2;
+    let (floor, ceil) = ((k.floor() as u32).max(1), (k.ceil() as u32).max(1));
+    if false_positive_rate(items_count, num_bits, floor)
+        <= false_positive_rate(items_count, num_bits, ceil)
+    {
+        floor
+    } else {
+        ceil
+    }
+}
+
+/// The two integers around the optimal number of hash functions `-log2(p)`.
+fn hashes_candidates(fp_rate: f64) -> impl Iterator<Item = u32> {
+    let k = -fp_rate.log2();
+    [(k.floor() as u32).max(1), (k.ceil() as u32).max(1)].into_iter()
+}
+
+fn round_to_words(num_bits: f64) -> Result<usize, BloomError> {
+    let words = (num_bits / WORD_BITS as f64).ceil();
+    // the float computation saturates instead of wrapping, so checking the upper bound is enough
+    if !words.is_finite() || words * WORD_BITS as f64 > BitSlice::<u64, Lsb0>::MAX_BITS as f64 {
+        return Err(BloomError::BitmapSizeOverflow);
+    }
+    Ok((words as usize).max(1) * WORD_BITS)
+}
+
+fn budget_to_bits(bytes: usize) -> Result<usize, BloomError> {
+    let num_bits = (bytes / (WORD_BITS / 8))
+        .checked_mul(WORD_BITS)
+        .ok_or(BloomError::BitmapSizeOverflow)?;
+    check_num_bits(num_bits)?;
+    Ok(num_bits)
+}
+
+/// Unwrap the `kind` of filter built by a fallible constructor, for the panicking constructors of all the filters.
+#[track_caller]
+pub(crate) fn expect_valid<F>(filter: Result<F, BloomError>, kind: &str) -> F {
+    match filter {
+        Ok(filter) => filter,
+        Err(err) => panic!("invalid {kind} parameters: {err}"),
+    }
+}
+
+pub(crate) fn check_items_count(items_count: usize) -> Result<(), BloomError> {
+    if items_count == 0 {
+        return Err(BloomError::ZeroItemsCount);
+    }
+    Ok(())
+}
+
+pub(crate) fn check_fp_rate(fp_rate: f64) -> Result<(), BloomError> {
+    if !is_in_unit_interval(fp_rate) {
+        return Err(BloomError::InvalidFpRate(fp_rate));
+    }
+    Ok(())
+}
+
+/// Whether `value` lies in the open interval `(0, 1)`, which NaN doesn't.
+pub(crate) fn is_in_unit_interval(value: f64) -> bool {
+    value > 0.0 && value < 1.0
+}
+
+/// The smallest number of fingerprint bits, at most `max_bits`, for which a lookup compared with `comparisons`
+/// fingerprints has a false positive rate of at most `fp_rate`.
+pub(crate) fn fingerprint_bits(
+    fp_rate: f64,
+    comparisons: usize,
+    max_bits: u32,
+) -> Result<u32, BloomError> {
+    check_fp_rate(fp_rate)?;
+    // each comparison matches with a probability of 1 / 2^bits
+    let bits = (comparisons as f64 / fp_rate).log2().ceil().max(1.0);
+    if bits > max_bits as f64 {
+        return Err(BloomError::InvalidFpRate(fp_rate));
+    }
+    Ok(bits as u32)
+}
+
+fn check_num_bits(num_bits: usize) -> Result<(), BloomError> {
+    if num_bits == 0 {
+        return Err(BloomError::ZeroBitsCount);
+    }
+    if num_bits > BitSlice::<u64, Lsb0>::MAX_BITS {
+        return Err(BloomError::BitmapSizeOverflow);
+    }
+    Ok(())
+}
+
+fn check_num_hashes(num_hashes: u32) -> Result<(), BloomError> {
+    if num_hashes == 0 {
+        return Err(BloomError::ZeroHashesCount);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= expected * tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn fp_rate_table() {
+        // (m / n, k, p) from the table of Fan, Cao, Almeida and Broder, "Summary Cache" (2000)
+        let table = [
+            (2, 1, 0.393),
+            (4, 3, 0.147),
+            (8, 6, 0.0216),
+            (10, 7, 0.00819),
+            (16, 11, 0.000459),
+            (32, 22, 2.10e-7),
+        ];
+        for (bits_per_item, num_hashes, fp_rate) in table {
+            let params = BloomParams::from_items_and_bits(1000, bits_per_item * 1000).unwrap();
+            assert_eq!(params.num_hashes(), num_hashes);
+            assert_close(params.fp_rate(), fp_rate, 0.005);
+        }
+    }
+
+    #[test]
+    fn bits_per_item_table() {
+        // optimal bits per item -ln(p) / ln(2)^2 and hashes -log2(p) for the usual targets
+        let table = [(0.1, 4.793, 3), (0.01, 9.585, 7), (0.001, 14.378, 10)];
+        for (fp_rate, bits_per_item, num_hashes) in table {
+            let params = BloomParams::from_items_and_fp_rate(1_000_000, fp_rate).unwrap();
+            assert_eq!(params.num_hashes(), num_hashes);
+            assert_close(params.num_bits() as f64 / 1e6, bits_per_item, 0.01);
+            assert_eq!(params.num_bits() % 64, 0);
+            assert!(params.fp_rate() <= fp_rate);
+
+            let params = BloomParams::from_bits_and_fp_rate(params.num_bits(), fp_rate).unwrap();
+            assert_eq!(params.num_hashes(), num_hashes);
+            assert!(params.items_count() >= 1_000_000);
+            assert!(params.fp_rate() <= fp_rate);
+        }
+    }
+
+    #[test]
+    fn blocked_bits_per_item() {
+        // blocks of a cache line need a few more bits per item than a standard filter, blocks of a word many more
+        let table = [
+            (512, 0.01, 9.92, 6),
+            (512, 0.0001, 22.03, 12),
+            (64, 0.01, 12.14, 5),
+            (64, 0.0001, 45.41, 9),
+        ];
+        for (block_bits, fp_rate, bits_per_item, num_hashes) in table {
+            let params =
+                BloomParams::from_items_and_fp_rate_blocked(1_000_000, fp_rate, block_bits, 64)
+                    .unwrap();
+            assert_eq!(params.num_hashes(), num_hashes);
+            assert_close(params.num_bits() as f64 / 1e6, bits_per_item, 0.01);
+            assert_eq!(params.num_bits() % block_bits, 0);
+            assert!(params.fp_rate() <= fp_rate);
+        }
+    }
+
+    #[test]
+    fn small_items_count() {
+        // a single word holds 64 bits per item, for which 44 hashes are optimal rather than -log2(0.01)
+        let params = BloomParams::from_items_and_fp_rate(1, 0.01).unwrap();
+        assert_eq!(params.num_bits(), 64);
+        assert_eq!(params.num_hashes(), 44);
+
+        for items_count in 1..=20 {
+            let params = BloomParams::from_items_and_fp_rate(items_count, 0.01).unwrap();
+            assert_eq!(
+                params.num_hashes(),
+                optimal_hashes(items_count, params.num_bits())
+            );
+            assert!(params.fp_rate() <= 0.01);
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let params = BloomParams::from_items_and_hashes(1000, 7).unwrap();
+        assert_eq!(params.num_bits(), 10_112);
+        assert_eq!(params.num_hashes(), 7);
+
+        let params = BloomParams::from_bits_and_hashes(params.num_bits(), 7).unwrap();
+        assert_eq!(params.items_count(), 1001);
+    }
+
+    #[test]
+    fn memory_budget() {
+        let params = BloomParams::from_memory_budget(1 << 20, 0.01).unwrap();
+        assert_eq!(params.num_bits(), 8 << 20);
+        assert!(params.fp_rate() <= 0.01);
+        assert_close(params.items_count() as f64, (8 << 20) as f64 / 9.585, 0.01);
+
+        let params = BloomParams::from_items_and_memory_budget(1000, 1203).unwrap();
+        assert_eq!(params.num_bits(), 9600);
+        assert_eq!(params.num_hashes(), 7);
+
+        assert_eq!(
+            BloomParams::from_memory_budget(7, 0.01),
+            Err(BloomError::ZeroBitsCount)
+        );
+        assert_eq!(
+            BloomParams::from_memory_budget(8, 1e-30),
+            Err(BloomError::CapacityTooSmall)
+        );
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert_eq!(
+            BloomParams::from_items_and_bits(0, 64),
+            Err(BloomError::ZeroItemsCount)
+        );
+        assert_eq!(
+            BloomParams::from_items_and_bits(10, 0),
+            Err(BloomError::ZeroBitsCount)
+        );
+        assert_eq!(
+            BloomParams::from_items_and_hashes(10, 0),
+            Err(BloomError::ZeroHashesCount)
+        );
+        assert_eq!(
+            BloomParams::from_bits_and_fp_rate(64, 1.0),
+            Err(BloomError::InvalidFpRate(1.0))
+        );
+        assert_eq!(
+            BloomParams::from_items_and_hashes(usize::MAX, u32::MAX),
+            Err(BloomError::BitmapSizeOverflow)
+        );
+    }
+}
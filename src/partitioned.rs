@@ -0,0 +1,249 @@
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+use bitvec::prelude::*;
+
+use crate::{hash, params, BloomError, BloomParams, SeededState};
+
+/// A bloom filter where each hash function owns its own slice of the bitmap.
+///
+/// The `m` bits of the filter are split into `k` slices of `m / k` bits, and the `i`-th hash function only sets
+/// bits in the `i`-th slice. Every item then sets exactly `k` distinct bits, which makes all hash functions behave
+/// uniformly and the fill of the filter more predictable, for a false positive rate very close to the one of a
+/// [`BloomFilter`](crate::BloomFilter) of the same size. This is the building block of
+/// [`ScalableBloomFilter`](crate::ScalableBloomFilter).
+///
+/// The index of an item in each slice is derived from a mix of its whole kernel hashes rather than from the double
+/// hashing of [`BloomFilter`](crate::BloomFilter), so that the tiny slices of a filter sized for a handful of items
+/// (a few bits each) still give independent indexes and meet the expected false positive rate.
+///
+/// Example usage:
+/// ```
+/// use bloom_filter::PartitionedBloomFilter;
+///
+/// let mut bloom = PartitionedBloomFilter::new(100, 0.01);
+/// bloom.insert("item");
+/// assert!(bloom.contains("item"));
+/// ```
+pub struct PartitionedBloomFilter<T: ?Sized, S = SeededState> {
+    bitmap: BitVec<u64, Lsb0>,
+    slice_len: u64,
+    optimal_k: u32,
+    capacity: usize,
+    inserted_count: u64,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized + Hash> PartitionedBloomFilter<T> {
+    /// Create a new PartitionedBloomFilter based on its size and the expected false positive rate, hashing items
+    /// with a random seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`PartitionedBloomFilter::try_new`] for a fallible version.
+    pub fn new(items_count: usize, fp_rate: f64) -> Self {
+        params::expect_valid(Self::try_new(items_count, fp_rate), "bloom filter")
+    }
+
+    /// Fallible version of [`PartitionedBloomFilter::new`], see [`BloomFilter::try_new`](crate::BloomFilter::try_new)
+    /// for the possible errors.
+    pub fn try_new(items_count: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        let params = BloomParams::from_items_and_fp_rate(items_count, fp_rate)?;
+        Ok(Self::with_params(params, SeededState::new()))
+    }
+
+    /// Create a new PartitionedBloomFilter hashing items deterministically from `seed`, see
+    /// [`BloomFilter::with_seed`](crate::BloomFilter::with_seed).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`PartitionedBloomFilter::try_with_seed`] for a fallible version.
+    pub fn with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Self {
+        params::expect_valid(
+            Self::try_with_seed(items_count, fp_rate, seed),
+            "bloom filter",
+        )
+    }
+
+    /// Fallible version of [`PartitionedBloomFilter::with_seed`], see
+    /// [`BloomFilter::try_new`](crate::BloomFilter::try_new) for the possible errors.
+    pub fn try_with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Result<Self, BloomError> {
+        let params = BloomParams::from_items_and_fp_rate(items_count, fp_rate)?;
+        Ok(Self::with_params(params, SeededState::with_seed(seed)))
+    }
+}
+
+impl<T: ?Sized + Hash, S: BuildHasher + 'static> PartitionedBloomFilter<T, S> {
+    /// Create a new PartitionedBloomFilter sized by `params`, hashing items with hashers built by `hash_builder`.
+    ///
+    /// The number of bits is rounded up to a multiple of the number of hash functions.
+    pub fn with_params(params: BloomParams, hash_builder: S) -> Self {
+        let slice_len = params.num_bits().div_ceil(params.num_hashes() as usize);
+
+        PartitionedBloomFilter {
+            bitmap: bitvec![u64, Lsb0; 0; slice_len * params.num_hashes() as usize],
+            slice_len: slice_len as u64,
+            optimal_k: params.num_hashes(),
+            capacity: params.items_count(),
+            inserted_count: 0,
+            hash_builder,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Insert an element into the filter.
+    pub fn insert(&mut self, item: &T) {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        self.insert_hashes(h1, h2);
+    }
+
+    /// Checks if an element is contained in the filter, see [`BloomFilter::contains`](crate::BloomFilter::contains).
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        self.contains_hashes(h1, h2)
+    }
+
+    /// Insert an element given its kernel hashes.
+    pub(crate) fn insert_hashes(&mut self, h1: u64, h2: u64) {
+        for k_i in 0..self.optimal_k {
+            let index = self.get_index(h1, h2, k_i);
+            self.bitmap.set(index, true);
+        }
+        self.inserted_count += 1;
+    }
+
+    /// Checks if an element is contained in the filter given its kernel hashes.
+    pub(crate) fn contains_hashes(&self, h1: u64, h2: u64) -> bool {
+        (0..self.optimal_k).all(|k_i| self.bitmap[self.get_index(h1, h2, k_i)])
+    }
+
+    fn get_index(&self, h1: u64, h2: u64, k_i: u32) -> usize {
+        // the k_i-th hash function indexes into the k_i-th slice. h1 + k_i * h2 modulo the slice length only
+        // depends on h1 and h2 modulo it, which leaves as few as slice_len² combinations of bits for small slices:
+        // mix the whole 64-bit hash before reducing it so that each slice gets an independent index
+        let hash = hash::mix(h1, (k_i as u64).wrapping_mul(h2));
+        k_i as usize * self.slice_len as usize + (hash % self.slice_len) as usize
+    }
+}
+
+impl<T: ?Sized, S> PartitionedBloomFilter<T, S> {
+    /// The number of bits of this filter.
+    pub fn num_bits(&self) -> usize {
+        self.bitmap.len()
+    }
+
+    /// The number of hash functions of this filter, which is also its number of slices.
+    pub fn num_hashes(&self) -> u32 {
+        self.optimal_k
+    }
+
+    /// The number of bits of each slice of this filter.
+    pub fn slice_len(&self) -> usize {
+        self.slice_len as usize
+    }
+
+    /// The number of items this filter was sized for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// The number of insertions performed on this filter, including repeated insertions of the same item.
+    pub fn inserted_count(&self) -> u64 {
+        self.inserted_count
+    }
+
+    /// The proportion of bits set in the filter, between 0 and 1.
+    pub fn fill_ratio(&self) -> f64 {
+        self.bitmap.count_ones() as f64 / self.bitmap.len() as f64
+    }
+
+    /// The hash builder used by this filter.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BloomFilter;
+
+    #[test]
+    fn insert() {
+        let mut bloom = PartitionedBloomFilter::with_seed(100, 0.01, 0);
+        assert_eq!(bloom.num_hashes(), 7);
+        assert_eq!(bloom.num_bits(), bloom.slice_len() * 7);
+        bloom.insert("item");
+        assert!(bloom.contains("item"));
+        assert!(!bloom.contains("other_item"));
+    }
+
+    #[test]
+    fn one_bit_per_slice() {
+        let mut bloom = PartitionedBloomFilter::with_seed(100, 0.01, 0);
+        bloom.insert("item");
+        for slice in bloom.bitmap.chunks(bloom.slice_len()) {
+            assert_eq!(slice.count_ones(), 1);
+        }
+    }
+
+    #[test]
+    fn small_slices() {
+        // a filter sized for a handful of items has slices of a few bits only
+        for (items_count, fp_rate) in [(1, 0.01), (5, 0.01), (5, 1e-5), (10, 0.001)] {
+            let mut bloom = PartitionedBloomFilter::with_seed(items_count, fp_rate, 0);
+            assert!(bloom.slice_len() <= 20);
+            for i in 0..items_count as u32 {
+                bloom.insert(&i);
+            }
+            let false_positives = (1_000..201_000u32).filter(|i| bloom.contains(i)).count();
+            let measured = false_positives as f64 / 200_000.0;
+            assert!(
+                measured < fp_rate * 1.2,
+                "measured fp rate {measured} for target {fp_rate}"
+            );
+        }
+    }
+
+    #[test]
+    fn fp_rate_compared_to_bloom_filter() {
+        let fp_rate = 0.01;
+        let mut partitioned = PartitionedBloomFilter::with_seed(10_000, fp_rate, 0);
+        let mut bloom = BloomFilter::with_seed(10_000, fp_rate, 0);
+        for i in 0..10_000u32 {
+            partitioned.insert(&i);
+            bloom.insert(&i);
+        }
+
+        let measure = |contains: &dyn Fn(&u32) -> bool| {
+            (10_000..210_000u32).filter(|i| contains(i)).count() as f64 / 200_000.0
+        };
+        let partitioned_fp_rate = measure(&|i| partitioned.contains(i));
+        let bloom_fp_rate = measure(&|i| bloom.contains(i));
+
+        assert!(partitioned_fp_rate < fp_rate * 1.2, "{partitioned_fp_rate}");
+        assert!(
+            (partitioned_fp_rate - bloom_fp_rate).abs() < fp_rate * 0.2,
+            "partitioned: {partitioned_fp_rate}, bloom: {bloom_fp_rate}"
+        );
+    }
+
+    #[test]
+    fn try_with_seed_rejects_invalid_parameters() {
+        assert_eq!(
+            PartitionedBloomFilter::<str>::try_with_seed(0, 0.01, 0).err(),
+            Some(BloomError::ZeroItemsCount)
+        );
+        assert!(matches!(
+            PartitionedBloomFilter::<str>::try_with_seed(100, f64::NAN, 0),
+            Err(BloomError::InvalidFpRate(fp_rate)) if fp_rate.is_nan()
+        ));
+    }
+}
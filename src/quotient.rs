@@ -0,0 +1,739 @@
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+use bitvec::prelude::*;
+
+use crate::{params, BloomError, SeededState};
+
+/// Proportion of the slots which can be filled before the filter is full, beyond which clusters get too long.
+const MAX_LOAD_FACTOR: f64 = 0.95;
+
+/// A quotient filter, a compact membership structure supporting deletions, resizing and merges.
+///
+/// Each item is hashed to a fingerprint of `quotient_bits + remainder_bits` bits: its quotient (the high bits) is
+/// the index of its canonical slot in a table of `2^quotient_bits` slots, and only its remainder (the low bits) is
+/// stored, in the canonical slot or shifted after it by linear probing. Three metadata bits per slot
+/// (`is_occupied`, `is_continuation` and `is_shifted`) keep track of which quotient every remainder belongs to, so
+/// the filter holds the exact fingerprints of its items and false positives only come from fingerprint
+/// collisions, with a probability of about `load_factor / 2^remainder_bits`.
+///
+/// Since the fingerprints can be recovered from the table, a quotient filter can do what a
+/// [`BloomFilter`](crate::BloomFilter) can't: it can double its number of slots by moving one bit of every
+/// remainder to its quotient ([`QuotientFilter::grow`]), and two filters can be merged into a larger one
+/// ([`QuotientFilter::merge`]), both without access to the original items.
+///
+/// Example usage:
+/// ```
+/// use bloom_filter::QuotientFilter;
+///
+/// let mut quotient = QuotientFilter::<str>::new(100, 0.01);
+/// quotient.insert("item").unwrap();
+/// assert!(quotient.contains("item"));
+/// assert!(quotient.remove("item"));
+/// assert!(!quotient.contains("item"));
+/// ```
+pub struct QuotientFilter<T: ?Sized, S = SeededState> {
+    remainders: BitVec<u64, Lsb0>,
+    occupieds: BitVec<u64, Lsb0>,
+    continuations: BitVec<u64, Lsb0>,
+    shifteds: BitVec<u64, Lsb0>,
+    quotient_bits: u32,
+    remainder_bits: u32,
+    len: usize,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized + Hash> QuotientFilter<T> {
+    /// Create a new QuotientFilter able to hold `items_count` items with the expected false positive rate, hashing
+    /// items with a random seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`QuotientFilter::try_new`] for a fallible version.
+    pub fn new(items_count: usize, fp_rate: f64) -> Self {
+        params::expect_valid(Self::try_new(items_count, fp_rate), "quotient filter")
+    }
+
+    /// Fallible version of [`QuotientFilter::new`].
+    ///
+    /// Returns an error if `items_count` is zero, if `fp_rate` is not in the open interval `(0, 1)` or needs
+    /// fingerprints longer than 64 bits, or if the filter would be too large to be allocated.
+    pub fn try_new(items_count: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        let (quotient_bits, remainder_bits) = bits_for(items_count, fp_rate)?;
+        Self::try_with_options(quotient_bits, remainder_bits, SeededState::new())
+    }
+
+    /// Create a new QuotientFilter hashing items deterministically from `seed`, see
+    /// [`BloomFilter::with_seed`](crate::BloomFilter::with_seed).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`QuotientFilter::try_with_seed`] for a fallible version.
+    pub fn with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Self {
+        params::expect_valid(
+            Self::try_with_seed(items_count, fp_rate, seed),
+            "quotient filter",
+        )
+    }
+
+    /// Fallible version of [`QuotientFilter::with_seed`], see [`QuotientFilter::try_new`] for the possible errors.
+    pub fn try_with_seed(items_count: usize, fp_rate: f64, seed: u64) -> Result<Self, BloomError> {
+        let (quotient_bits, remainder_bits) = bits_for(items_count, fp_rate)?;
+        Self::try_with_options(quotient_bits, remainder_bits, SeededState::with_seed(seed))
+    }
+}
+
+impl<T: ?Sized + Hash, S: BuildHasher> QuotientFilter<T, S> {
+    /// Create a new QuotientFilter of `2^quotient_bits` slots storing remainders of `remainder_bits` bits, hashing
+    /// items with hashers built by `hash_builder`.
+    ///
+    /// The filter holds up to 95% of its number of slots, and takes `remainder_bits + 3` bits per slot.
+    ///
+    /// Returns an error if `quotient_bits` is not in `1..=63`, if `remainder_bits` is zero or longer than
+    /// `64 - quotient_bits`, or if the filter would be too large to be allocated.
+    pub fn try_with_options(
+        quotient_bits: u32,
+        remainder_bits: u32,
+        hash_builder: S,
+    ) -> Result<Self, BloomError> {
+        if !(1..=63).contains(&quotient_bits) {
+            return Err(BloomError::InvalidQuotientBits(quotient_bits));
+        }
+        if remainder_bits == 0 || quotient_bits + remainder_bits > 64 {
+            return Err(BloomError::InvalidRemainderBits(remainder_bits));
+        }
+        let num_slots = num_slots(quotient_bits, remainder_bits)?;
+
+        Ok(QuotientFilter {
+            remainders: bitvec![u64, Lsb0; 0; num_slots * remainder_bits as usize],
+            occupieds: bitvec![u64, Lsb0; 0; num_slots],
+            continuations: bitvec![u64, Lsb0; 0; num_slots],
+            shifteds: bitvec![u64, Lsb0; 0; num_slots],
+            quotient_bits,
+            remainder_bits,
+            len: 0,
+            hash_builder,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Insert an element into the filter.
+    ///
+    /// Inserting the same element several times stores several copies of its fingerprint, which must all be
+    /// removed for the element to be removed. Returns [`BloomError::FilterFull`] and leaves the filter untouched if
+    /// the filter already holds [`QuotientFilter::capacity`] items, see [`QuotientFilter::grow`] to make room.
+    pub fn insert(&mut self, item: &T) -> Result<(), BloomError> {
+        self.insert_fingerprint(self.fingerprint(item))
+    }
+
+    /// Checks if an element is contained in the filter, see [`BloomFilter::contains`](crate::BloomFilter::contains).
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.contains_fingerprint(self.fingerprint(item))
+    }
+
+    /// Remove an element from the filter, returning whether it was found.
+    ///
+    /// Only elements which were inserted before can be removed: removing an element which is only a false
+    /// positive of the filter removes the fingerprint of another element, which is then no longer found.
+    pub fn remove<Q: ?Sized + Hash>(&mut self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.remove_fingerprint(self.fingerprint(item))
+    }
+
+    /// The fingerprint of an element: the `quotient_bits + remainder_bits` high bits of its hash.
+    fn fingerprint<Q: ?Sized + Hash>(&self, item: &Q) -> u64 {
+        self.hash_builder.hash_one(item) >> (64 - self.fingerprint_bits())
+    }
+}
+
+impl<T: ?Sized, S: PartialEq> QuotientFilter<T, S> {
+    /// Checks if two filters can be merged, i.e. if they have fingerprints of the same length and the same
+    /// hashers (for instance the same seed), so that an item has the same fingerprint in both.
+    ///
+    /// Filters with different numbers of slots are compatible, as long as the sum of their quotient and remainder
+    /// bits is the same, for instance after growing one of them.
+    pub fn is_compatible(&self, other: &Self) -> bool {
+        self.fingerprint_bits() == other.fingerprint_bits()
+            && self.hash_builder == other.hash_builder
+    }
+
+    /// Merge `other` into this filter, which then contains every item of both filters.
+    ///
+    /// The fingerprints of both filters are read back from their tables, so the original items are not needed.
+    /// This filter grows as many times as needed to hold the items of both filters, see
+    /// [`QuotientFilter::grow`].
+    ///
+    /// Returns an error and leaves this filter untouched if the filters are not compatible (see
+    /// [`QuotientFilter::is_compatible`]), or [`BloomError::FilterFull`] if this filter can't grow enough.
+    pub fn merge(&mut self, other: &Self) -> Result<(), BloomError> {
+        if !self.is_compatible(other) {
+            return Err(BloomError::IncompatibleFilters);
+        }
+
+        let len = self.len + other.len;
+        let mut quotient_bits = self.quotient_bits;
+        while len > capacity(1 << quotient_bits) {
+            quotient_bits += 1;
+            if quotient_bits >= self.fingerprint_bits() {
+                return Err(BloomError::FilterFull);
+            }
+        }
+
+        let mut fingerprints = self.fingerprints();
+        fingerprints.extend(other.fingerprints());
+        self.rebuild(quotient_bits, &fingerprints)
+    }
+}
+
+impl<T: ?Sized, S> QuotientFilter<T, S> {
+    /// The number of items in the filter.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the filter holds no item.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of items this filter can hold before it's full, 95% of its number of slots.
+    pub fn capacity(&self) -> usize {
+        capacity(self.num_slots())
+    }
+
+    /// The number of slots of this filter, `2^quotient_bits`.
+    pub fn num_slots(&self) -> usize {
+        self.occupieds.len()
+    }
+
+    /// The proportion of slots of the filter holding a remainder, between 0 and 1.
+    pub fn load_factor(&self) -> f64 {
+        self.len as f64 / self.num_slots() as f64
+    }
+
+    /// The number of bits of the quotients of this filter.
+    pub fn quotient_bits(&self) -> u32 {
+        self.quotient_bits
+    }
+
+    /// The number of bits of the remainders of this filter.
+    pub fn remainder_bits(&self) -> u32 {
+        self.remainder_bits
+    }
+
+    /// The number of bits of the fingerprints of this filter, which doesn't change when it grows.
+    pub fn fingerprint_bits(&self) -> u32 {
+        self.quotient_bits + self.remainder_bits
+    }
+
+    /// The number of bits of this filter, remainders and metadata included.
+    pub fn num_bits(&self) -> usize {
+        self.num_slots() * (self.remainder_bits as usize + 3)
+    }
+
+    /// The hash builder used by this filter.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Double the number of slots of the filter, keeping all its items.
+    ///
+    /// The high bit of every remainder becomes the low bit of its quotient, so the fingerprints of the items
+    /// don't change, but remainders are one bit shorter and the false positive rate for a given load factor
+    /// doubles. Returns [`BloomError::FilterFull`] and leaves the filter untouched if remainders are a single
+    /// bit long, or [`BloomError::BitmapSizeOverflow`] if the grown filter would be too large to be allocated.
+    pub fn grow(&mut self) -> Result<(), BloomError> {
+        if self.remainder_bits == 1 {
+            return Err(BloomError::FilterFull);
+        }
+        let fingerprints = self.fingerprints();
+        self.rebuild(self.quotient_bits + 1, &fingerprints)
+    }
+
+    /// Replace the table of the filter with one of `2^quotient_bits` slots holding `fingerprints`, leaving the
+    /// filter untouched if it can't be allocated.
+    fn rebuild(&mut self, quotient_bits: u32, fingerprints: &[u64]) -> Result<(), BloomError> {
+        let remainder_bits = self.fingerprint_bits() - quotient_bits;
+        let num_slots = num_slots(quotient_bits, remainder_bits)?;
+
+        self.remainders = bitvec![u64, Lsb0; 0; num_slots * remainder_bits as usize];
+        self.occupieds = bitvec![u64, Lsb0; 0; num_slots];
+        self.continuations = bitvec![u64, Lsb0; 0; num_slots];
+        self.shifteds = bitvec![u64, Lsb0; 0; num_slots];
+        self.quotient_bits = quotient_bits;
+        self.remainder_bits = remainder_bits;
+        self.len = 0;
+        for &fingerprint in fingerprints {
+            self.insert_fingerprint(fingerprint)
+                .expect("the filter was sized for all the fingerprints");
+        }
+        Ok(())
+    }
+
+    /// The fingerprints of all the items of the filter, read back from its table.
+    fn fingerprints(&self) -> Vec<u64> {
+        let mut fingerprints = Vec::with_capacity(self.len);
+        // start right after an empty slot, so that the first remainder found starts a cluster
+        let Some(empty) = (0..self.num_slots()).find(|&slot| self.is_empty_slot(slot)) else {
+            return fingerprints;
+        };
+        let mut quotient = empty;
+        let mut slot = empty;
+        for _ in 0..self.num_slots() {
+            slot = self.next(slot);
+            if self.is_empty_slot(slot) {
+                continue;
+            }
+            if !self.shifteds[slot] {
+                // a remainder in its canonical slot starts a cluster
+                quotient = slot;
+            } else if !self.continuations[slot] {
+                // other runs belong to the following occupied quotients
+                quotient = self.next_occupied(quotient);
+            }
+            fingerprints.push(((quotient as u64) << self.remainder_bits) | self.remainder(slot));
+        }
+        fingerprints
+    }
+
+    fn insert_fingerprint(&mut self, fingerprint: u64) -> Result<(), BloomError> {
+        if self.len >= self.capacity() {
+            return Err(BloomError::FilterFull);
+        }
+        let (quotient, remainder) = self.split(fingerprint);
+        if self.is_empty_slot(quotient) {
+            self.occupieds.set(quotient, true);
+            self.set_remainder(quotient, remainder);
+            self.len += 1;
+            return Ok(());
+        }
+
+        let was_occupied = self.occupieds[quotient];
+        self.occupieds.set(quotient, true);
+        let run_start = self.run_start(quotient);
+        let mut slot = run_start;
+        if was_occupied {
+            // the remainders of a run are kept sorted
+            while self.remainder(slot) < remainder {
+                slot = self.next(slot);
+                if !self.continuations[slot] {
+                    break;
+                }
+            }
+        }
+
+        self.shift_right(slot, remainder, slot != run_start, slot != quotient);
+        if was_occupied && slot == run_start {
+            // the previous head of the run is now its second remainder
+            let next = self.next(slot);
+            self.continuations.set(next, true);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    fn contains_fingerprint(&self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        self.find(quotient, remainder).is_some()
+    }
+
+    fn remove_fingerprint(&mut self, fingerprint: u64) -> bool {
+        let (quotient, remainder) = self.split(fingerprint);
+        let Some((run_start, slot)) = self.find(quotient, remainder) else {
+            return false;
+        };
+
+        let is_head = slot == run_start;
+        if is_head && !self.continuations[self.next(slot)] {
+            // this was the only remainder of the run
+            self.occupieds.set(quotient, false);
+        }
+        self.shift_left(slot, quotient, is_head);
+        self.len -= 1;
+        true
+    }
+
+    /// The start of the run of `quotient` and the slot holding `remainder` in this run, if any.
+    fn find(&self, quotient: usize, remainder: u64) -> Option<(usize, usize)> {
+        if !self.occupieds[quotient] {
+            return None;
+        }
+        let run_start = self.run_start(quotient);
+        let mut slot = run_start;
+        loop {
+            match self.remainder(slot).cmp(&remainder) {
+                Ordering::Less => {}
+                Ordering::Equal => return Some((run_start, slot)),
+                Ordering::Greater => return None,
+            }
+            slot = self.next(slot);
+            if !self.continuations[slot] {
+                return None;
+            }
+        }
+    }
+
+    /// The slot where the run of `quotient` starts, or would start if `quotient` is occupied but has no run yet.
+    fn run_start(&self, quotient: usize) -> usize {
+        // walk back to the start of the cluster, where a remainder lies in its canonical slot
+        let mut cluster_quotient = quotient;
+        while self.shifteds[cluster_quotient] {
+            cluster_quotient = self.prev(cluster_quotient);
+        }
+        // then skip the runs of the occupied quotients before `quotient`
+        let mut slot = cluster_quotient;
+        while cluster_quotient != quotient {
+            loop {
+                slot = self.next(slot);
+                if !self.continuations[slot] {
+                    break;
+                }
+            }
+            cluster_quotient = self.next_occupied(cluster_quotient);
+        }
+        slot
+    }
+
+    /// Write a remainder in `slot`, shifting the following remainders one slot to the right up to the next empty
+    /// slot.
+    fn shift_right(&mut self, slot: usize, remainder: u64, continuation: bool, shifted: bool) {
+        let (mut remainder, mut continuation, mut shifted) = (remainder, continuation, shifted);
+        let mut slot = slot;
+        loop {
+            let was_empty = self.is_empty_slot(slot);
+            let previous = (self.remainder(slot), self.continuations[slot]);
+            self.set_remainder(slot, remainder);
+            self.continuations.set(slot, continuation);
+            self.shifteds.set(slot, shifted);
+            if was_empty {
+                return;
+            }
+            (remainder, continuation) = previous;
+            shifted = true;
+            slot = self.next(slot);
+        }
+    }
+
+    /// Remove the remainder of `slot`, which belongs to `quotient`, shifting the following remainders one slot to
+    /// the left until one is in its canonical slot or the next slot is empty.
+    fn shift_left(&mut self, slot: usize, quotient: usize, is_head: bool) {
+        let mut quotient = quotient;
+        let mut slot = slot;
+        let mut is_head = is_head;
+        loop {
+            let next = self.next(slot);
+            if !self.shifteds[next] {
+                self.set_remainder(slot, 0);
+                self.continuations.set(slot, false);
+                self.shifteds.set(slot, false);
+                return;
+            }
+            if !self.continuations[next] {
+                quotient = self.next_occupied(quotient);
+            }
+            self.set_remainder(slot, self.remainder(next));
+            // the remainder following a removed head becomes the head of the run
+            let continuation = self.continuations[next] && !is_head;
+            self.continuations.set(slot, continuation);
+            self.shifteds.set(slot, slot != quotient);
+            is_head = false;
+            slot = next;
+        }
+    }
+
+    /// Split a fingerprint into its quotient and remainder.
+    fn split(&self, fingerprint: u64) -> (usize, u64) {
+        let quotient = (fingerprint >> self.remainder_bits) as usize;
+        let remainder = fingerprint & (u64::MAX >> (64 - self.remainder_bits));
+        (quotient, remainder)
+    }
+
+    fn is_empty_slot(&self, slot: usize) -> bool {
+        !self.occupieds[slot] && !self.continuations[slot] && !self.shifteds[slot]
+    }
+
+    fn next_occupied(&self, quotient: usize) -> usize {
+        let mut quotient = self.next(quotient);
+        while !self.occupieds[quotient] {
+            quotient = self.next(quotient);
+        }
+        quotient
+    }
+
+    fn next(&self, slot: usize) -> usize {
+        (slot + 1) & (self.num_slots() - 1)
+    }
+
+    fn prev(&self, slot: usize) -> usize {
+        slot.wrapping_sub(1) & (self.num_slots() - 1)
+    }
+
+    fn remainder(&self, slot: usize) -> u64 {
+        let bits = self.remainder_bits as usize;
+        self.remainders[slot * bits..(slot + 1) * bits].load_le()
+    }
+
+    fn set_remainder(&mut self, slot: usize, remainder: u64) {
+        let bits = self.remainder_bits as usize;
+        self.remainders[slot * bits..(slot + 1) * bits].store_le(remainder);
+    }
+}
+
+/// The number of items a filter of `num_slots` slots can hold.
+fn capacity(num_slots: usize) -> usize {
+    (num_slots as f64 * MAX_LOAD_FACTOR) as usize
+}
+
+/// The number of slots of a filter with `quotient_bits` and `remainder_bits`, if it can be allocated.
+fn num_slots(quotient_bits: u32, remainder_bits: u32) -> Result<usize, BloomError> {
+    1usize
+        .checked_shl(quotient_bits)
+        .filter(|num_slots| {
+            num_slots
+                .checked_mul(remainder_bits as usize)
+                .is_some_and(|bits| bits <= BitSlice::<u64, Lsb0>::MAX_BITS)
+        })
+        .ok_or(BloomError::BitmapSizeOverflow)
+}
+
+/// The quotient and remainder bits of a filter holding `items_count` items with a false positive rate of `fp_rate`.
+fn bits_for(items_count: usize, fp_rate: f64) -> Result<(u32, u32), BloomError> {
+    params::check_items_count(items_count)?;
+    params::check_fp_rate(fp_rate)?;
+
+    let mut quotient_bits = 1;
+    while capacity(1 << quotient_bits) < items_count {
+        quotient_bits += 1;
+        if quotient_bits >= 63 {
+            return Err(BloomError::BitmapSizeOverflow);
+        }
+    }
+    // a lookup matches a stored fingerprint with a probability of about load_factor / 2^remainder_bits
+    let remainder_bits = params::fingerprint_bits(fp_rate, 1, 64 - quotient_bits)?;
+    Ok((quotient_bits, remainder_bits))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    #[test]
+    fn insert_and_remove() {
+        let mut quotient = QuotientFilter::with_seed(1000, 0.001, 0);
+        for i in 0..1000u32 {
+            quotient.insert(&i).unwrap();
+        }
+        assert_eq!(quotient.len(), 1000);
+        assert!((0..1000u32).all(|i| quotient.contains(&i)));
+
+        for i in 0..500u32 {
+            assert!(quotient.remove(&i));
+        }
+        assert_eq!(quotient.len(), 500);
+        assert!((500..1000u32).all(|i| quotient.contains(&i)));
+        let still_contained = (0..500u32).filter(|i| quotient.contains(i)).count();
+        assert!(still_contained < 5, "{still_contained}");
+
+        for i in 500..1000u32 {
+            assert!(quotient.remove(&i));
+        }
+        assert!(quotient.is_empty());
+        assert!(quotient.occupieds.not_any());
+        assert!(quotient.continuations.not_any());
+        assert!(quotient.shifteds.not_any());
+    }
+
+    #[test]
+    fn duplicates() {
+        let mut quotient = QuotientFilter::with_seed(100, 0.01, 0);
+        quotient.insert("item").unwrap();
+        quotient.insert("item").unwrap();
+        assert_eq!(quotient.len(), 2);
+        assert!(quotient.remove("item"));
+        assert!(quotient.contains("item"));
+        assert!(quotient.remove("item"));
+        assert!(!quotient.contains("item"));
+        assert!(!quotient.remove("item"));
+    }
+
+    #[test]
+    fn matches_multiset_of_fingerprints() {
+        // a tiny table with short fingerprints, so that runs and clusters are long and wrap around the table
+        let mut quotient =
+            QuotientFilter::<u32>::try_with_options(5, 3, SeededState::with_seed(0)).unwrap();
+        let mut model = BTreeMap::<u64, usize>::new();
+        let mut rng = 0x9e37_79b9_7f4a_7c15u64;
+        for step in 0..20_000 {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            let fingerprint = rng % (1 << 8);
+            let count = model.entry(fingerprint).or_default();
+            if rng >> 60 < 7 {
+                assert_eq!(quotient.remove_fingerprint(fingerprint), *count > 0);
+                *count = count.saturating_sub(1);
+            } else if quotient.insert_fingerprint(fingerprint).is_ok() {
+                *count += 1;
+            }
+
+            assert_eq!(quotient.len(), model.values().sum::<usize>());
+            assert_eq!(
+                quotient.contains_fingerprint(fingerprint),
+                model[&fingerprint] > 0
+            );
+            if step % 100 == 0 {
+                for fingerprint in 0..1 << 8 {
+                    assert_eq!(
+                        quotient.contains_fingerprint(fingerprint),
+                        model.get(&fingerprint).is_some_and(|count| *count > 0)
+                    );
+                }
+            }
+        }
+
+        let mut fingerprints = quotient.fingerprints();
+        fingerprints.sort_unstable();
+        let expected: Vec<u64> = model
+            .iter()
+            .flat_map(|(fingerprint, count)| std::iter::repeat_n(*fingerprint, *count))
+            .collect();
+        assert_eq!(fingerprints, expected);
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        for fp_rate in [0.05, 0.01, 0.001] {
+            let mut quotient = QuotientFilter::with_seed(10_000, fp_rate, 0);
+            for i in 0..10_000u32 {
+                quotient.insert(&i).unwrap();
+            }
+            let false_positives = (10_000..210_000u32)
+                .filter(|i| quotient.contains(i))
+                .count();
+            let measured = false_positives as f64 / 200_000.0;
+            assert!(
+                measured < fp_rate,
+                "measured fp rate {measured} for target {fp_rate}"
+            );
+        }
+    }
+
+    #[test]
+    fn full() {
+        let mut quotient =
+            QuotientFilter::try_with_options(6, 10, SeededState::with_seed(0)).unwrap();
+        assert_eq!(quotient.capacity(), 60);
+        for i in 0..60u32 {
+            quotient.insert(&i).unwrap();
+        }
+        assert_eq!(quotient.insert(&60), Err(BloomError::FilterFull));
+        assert_eq!(quotient.len(), 60);
+        assert!((0..60u32).all(|i| quotient.contains(&i)));
+    }
+
+    #[test]
+    fn grow() {
+        let mut quotient =
+            QuotientFilter::try_with_options(10, 12, SeededState::with_seed(0)).unwrap();
+        for i in 0..quotient.capacity() as u32 {
+            quotient.insert(&i).unwrap();
+        }
+        let len = quotient.len() as u32;
+
+        quotient.grow().unwrap();
+        assert_eq!(quotient.num_slots(), 2048);
+        assert_eq!(quotient.quotient_bits(), 11);
+        assert_eq!(quotient.remainder_bits(), 11);
+        assert_eq!(quotient.len(), len as usize);
+        assert!((0..len).all(|i| quotient.contains(&i)));
+
+        for i in len..2 * len {
+            quotient.insert(&i).unwrap();
+        }
+        assert!((0..2 * len).all(|i| quotient.contains(&i)));
+
+        let mut quotient =
+            QuotientFilter::try_with_options(4, 1, SeededState::with_seed(0)).unwrap();
+        quotient.insert(&0).unwrap();
+        assert_eq!(quotient.grow(), Err(BloomError::FilterFull));
+        assert_eq!(quotient.num_slots(), 16);
+        assert!(quotient.contains(&0));
+    }
+
+    #[test]
+    fn merge() {
+        let mut left = QuotientFilter::try_with_options(10, 10, SeededState::with_seed(0)).unwrap();
+        let mut right = QuotientFilter::try_with_options(8, 12, SeededState::with_seed(0)).unwrap();
+        for i in 0..900u32 {
+            left.insert(&i).unwrap();
+        }
+        for i in 900..1100u32 {
+            right.insert(&i).unwrap();
+        }
+
+        left.merge(&right).unwrap();
+        assert_eq!(left.len(), 1100);
+        assert_eq!(left.quotient_bits(), 11);
+        assert_eq!(left.fingerprint_bits(), 20);
+        assert!((0..1100u32).all(|i| left.contains(&i)));
+
+        let other_seed =
+            QuotientFilter::<u32>::try_with_options(10, 10, SeededState::with_seed(1)).unwrap();
+        assert_eq!(
+            left.merge(&other_seed),
+            Err(BloomError::IncompatibleFilters)
+        );
+        assert_eq!(
+            left.merge(
+                &QuotientFilter::try_with_options(10, 12, SeededState::with_seed(0)).unwrap()
+            ),
+            Err(BloomError::IncompatibleFilters)
+        );
+        assert_eq!(left.len(), 1100);
+    }
+
+    #[test]
+    fn try_new_rejects_invalid_parameters() {
+        assert_eq!(
+            QuotientFilter::<str>::try_new(0, 0.01).err(),
+            Some(BloomError::ZeroItemsCount)
+        );
+        assert_eq!(
+            QuotientFilter::<str>::try_new(100, 1.0).err(),
+            Some(BloomError::InvalidFpRate(1.0))
+        );
+        assert_eq!(
+            QuotientFilter::<str>::try_with_seed(100, 0.0, 0).err(),
+            Some(BloomError::InvalidFpRate(0.0))
+        );
+        let state = SeededState::with_seed(0);
+        assert_eq!(
+            QuotientFilter::<str>::try_with_options(0, 8, state).err(),
+            Some(BloomError::InvalidQuotientBits(0))
+        );
+        assert_eq!(
+            QuotientFilter::<str>::try_with_options(10, 0, state).err(),
+            Some(BloomError::InvalidRemainderBits(0))
+        );
+        assert_eq!(
+            QuotientFilter::<str>::try_with_options(40, 25, state).err(),
+            Some(BloomError::InvalidRemainderBits(25))
+        );
+        assert_eq!(
+            QuotientFilter::<str>::try_with_options(62, 2, state).err(),
+            Some(BloomError::BitmapSizeOverflow)
+        );
+    }
+}
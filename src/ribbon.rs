@@ -0,0 +1,551 @@
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+};
+
+use bitvec::prelude::*;
+
+use crate::{hash, params, xor, BloomError, SeededState};
+
+/// Default number of coefficients of the equation of each key.
+const DEFAULT_BAND_WIDTH: u32 = 128;
+
+/// Number of random equations checked against the equations of the keys of a homogeneous filter with up to 9
+/// result bits, doubled with each further result bit up to [`MAX_PROBES`].
+const PROBES: u64 = 4096;
+
+/// Largest number of random equations checked against the equations of the keys of a homogeneous filter.
+const MAX_PROBES: u64 = PROBES << 8;
+
+/// The two kinds of [`RibbonFilter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RibbonVariant {
+    /// The equation of each key is solved for a fingerprint of the key: the false positive rate is exactly
+    /// `1 / 2^result_bits`, but the construction can fail and be retried with another seed.
+    Standard,
+    /// The equation of each key is solved for zero, and the free variables of the solution are random: the
+    /// construction never fails, for a false positive rate slightly above `1 / 2^result_bits`.
+    ///
+    /// The equations of some items are combinations of the equations of the keys, and these items are always
+    /// false positives. Homogeneous filters take `4 / band_width` more slots per key and one more band to make
+    /// them rare, and the construction tries other seeds while more than an eighth of the false positive rate
+    /// comes from them, measured with random equations.
+    ///
+    /// Measuring smaller false positive rates takes more equations: from 4096 for up to 9 result bits, doubling
+    /// with each further bit up to about a million for 17 result bits and more, which then dominates the cost of
+    /// building small filters. Above 17 result bits, a seed is accepted with one implied equation out of a
+    /// million, so these items may add up to about `1 / 2^20` to the false positive rate.
+    Homogeneous,
+}
+
+/// A ribbon filter, a static membership structure built once from a set of keys with near optimal space usage.
+///
+/// This is the filter of Dillinger and Walzer (2021). Each key is hashed to a start slot and to `band_width`
+/// random coefficients over GF(2), defining a linear equation over a band of `band_width` consecutive slots of
+/// `result_bits` bits. The filter is a solution of the equations of all the keys, found by an on-the-fly gaussian
+/// elimination which keeps the system banded, and lookups check that the slots of an item satisfy its equation.
+///
+/// The filter takes `result_bits` bits per slot, with only a few percent more slots than keys with the default
+/// band width of 128: about 7.5 bits per key for a million keys and a false positive rate of 0.8%, where a
+/// [`BloomFilter`](crate::BloomFilter) needs 10 bits per key. Narrower bands make construction and lookups faster
+/// but need more slots per key. Keys can't be added once the filter is built.
+///
+/// Example usage:
+/// ```
+/// use bloom_filter::RibbonFilter;
+///
+/// let keys: Vec<u64> = (0..1000).collect();
+/// let ribbon = RibbonFilter::<u64>::from_keys(&keys, 0.01);
+/// assert!(keys.iter().all(|key| ribbon.contains(key)));
+/// ```
+pub struct RibbonFilter<T: ?Sized, S = SeededState> {
+    // `result_bits` columns of `num_slots` bits, the i-th column holding the i-th bit of every slot
+    solution: BitVec<u64, Lsb0>,
+    num_slots: usize,
+    result_bits: u32,
+    band_width: u32,
+    variant: RibbonVariant,
+    seed: u64,
+    len: usize,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T: ?Sized + Hash> RibbonFilter<T> {
+    /// Build a standard RibbonFilter holding `keys` with the expected false positive rate, hashing them with a
+    /// random seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid or if the filter can't be built, see
+    /// [`RibbonFilter::try_with_options`].
+    pub fn from_keys<'a, I>(keys: I, fp_rate: f64) -> Self
+    where
+        T: 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        Self::with_hasher(keys, fp_rate, SeededState::new())
+    }
+
+    /// Build a standard RibbonFilter holding `keys` with the expected false positive rate, hashing them
+    /// deterministically from `seed`, see [`BloomFilter::with_seed`](crate::BloomFilter::with_seed).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid or if the filter can't be built, see
+    /// [`RibbonFilter::try_with_options`].
+    pub fn with_seed<'a, I>(keys: I, fp_rate: f64, seed: u64) -> Self
+    where
+        T: 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        Self::with_hasher(keys, fp_rate, SeededState::with_seed(seed))
+    }
+}
+
+impl<T: ?Sized + Hash, S: BuildHasher> RibbonFilter<T, S> {
+    /// Build a standard RibbonFilter holding `keys` with the expected false positive rate, hashing them with
+    /// hashers built by `hash_builder`.
+    ///
+    /// The results are just long enough to reach `fp_rate`, and bands are 128 coefficients wide.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid or if the filter can't be built, see
+    /// [`RibbonFilter::try_with_options`].
+    pub fn with_hasher<'a, I>(keys: I, fp_rate: f64, hash_builder: S) -> Self
+    where
+        T: 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        let ribbon = result_bits_for(fp_rate).and_then(|result_bits| {
+            Self::try_with_options(
+                keys,
+                result_bits,
+                DEFAULT_BAND_WIDTH,
+                RibbonVariant::Standard,
+                hash_builder,
+            )
+        });
+        match ribbon {
+            Ok(ribbon) => ribbon,
+            Err(err) => panic!("failed to build ribbon filter: {err}"),
+        }
+    }
+
+    /// Build a RibbonFilter holding `keys`, with results of `result_bits` bits and bands of `band_width`
+    /// coefficients, hashing them with hashers built by `hash_builder`.
+    ///
+    /// Duplicate keys are ignored. Returns an error if `result_bits` is not in `1..=32` or if `band_width` is not
+    /// in `32..=128`, [`BloomError::ConstructionFailed`] if the equations of a [`RibbonVariant::Standard`] filter
+    /// are inconsistent with each of the 100 construction seeds, or [`BloomError::BitmapSizeOverflow`] if there are
+    /// more than `2^32` keys. A construction seed fails for up to about one in five sets of keys with bands of 32
+    /// coefficients, and a few in a hundred with wider bands, so running out of seeds is very unlikely.
+    pub fn try_with_options<'a, I>(
+        keys: I,
+        result_bits: u32,
+        band_width: u32,
+        variant: RibbonVariant,
+        hash_builder: S,
+    ) -> Result<Self, BloomError>
+    where
+        T: 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        if !(1..=32).contains(&result_bits) {
+            return Err(BloomError::InvalidFingerprintBits(result_bits));
+        }
+        if !(32..=128).contains(&band_width) {
+            return Err(BloomError::InvalidBandWidth(band_width));
+        }
+
+        let key_hashes = xor::unique_hashes(&hash_builder, keys)?;
+        let mut ribbon = RibbonFilter {
+            solution: BitVec::new(),
+            num_slots: num_slots(key_hashes.len(), band_width, variant),
+            result_bits,
+            band_width,
+            variant,
+            seed: 0,
+            len: key_hashes.len(),
+            hash_builder,
+            _marker: PhantomData,
+        };
+
+        // an implied probe is a false positive whatever the result bits, allow them an eighth of the fp rate, and
+        // at least one probe when there are too few to measure it
+        let max_implied = (num_probes(result_bits) >> (result_bits + 3)).max(1);
+        let mut best = None;
+        for seed in xor::construction_seeds() {
+            ribbon.seed = seed;
+            if let Some((rows, results)) = ribbon.band(&key_hashes) {
+                let implied = match variant {
+                    RibbonVariant::Standard => 0,
+                    RibbonVariant::Homogeneous => ribbon.implied_probes(&rows),
+                };
+                if implied <= max_implied {
+                    ribbon.solution = ribbon.back_substitute(&rows, &results);
+                    return Ok(ribbon);
+                }
+                if best.is_none_or(|(fewest, _)| implied < fewest) {
+                    best = Some((implied, seed));
+                }
+            }
+        }
+
+        // homogeneous filters keep the seed with the fewest implied probes rather than failing
+        let (_, seed) = best.ok_or(BloomError::ConstructionFailed)?;
+        ribbon.seed = seed;
+        let (rows, results) = ribbon
+            .band(&key_hashes)
+            .ok_or(BloomError::ConstructionFailed)?;
+        ribbon.solution = ribbon.back_substitute(&rows, &results);
+        Ok(ribbon)
+    }
+
+    /// Checks if an element is contained in the filter, see [`BloomFilter::contains`](crate::BloomFilter::contains).
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (start, coefficients, result) = self.equation(self.hash_builder.hash_one(item));
+        let band = self.band_width as usize;
+        (0..self.result_bits as usize).all(|bit| {
+            let column = bit * self.num_slots + start;
+            let slots: u128 = self.solution[column..column + band].load_le();
+            (slots & coefficients).count_ones() % 2 == (result >> bit) & 1
+        })
+    }
+}
+
+impl<T: ?Sized, S> RibbonFilter<T, S> {
+    /// The number of distinct keys the filter was built from.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the filter was built from no key.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of slots of this filter, a few percent more than its number of keys.
+    pub fn num_slots(&self) -> usize {
+        self.num_slots
+    }
+
+    /// The number of bits of the results of this filter.
+    pub fn result_bits(&self) -> u32 {
+        self.result_bits
+    }
+
+    /// The number of coefficients of the equation of each key.
+    pub fn band_width(&self) -> u32 {
+        self.band_width
+    }
+
+    /// The variant of this filter.
+    pub fn variant(&self) -> RibbonVariant {
+        self.variant
+    }
+
+    /// The number of bits of this filter.
+    pub fn num_bits(&self) -> usize {
+        self.solution.len()
+    }
+
+    /// The hash builder used by this filter.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// Bring the equations of all the keys with the current seed to echelon form, returning the coefficients and
+    /// the result of the row of each slot, or `None` if they are inconsistent.
+    fn band(&self, key_hashes: &[u64]) -> Option<(Vec<u128>, Vec<u32>)> {
+        // every row of the system has its first coefficient on the diagonal, so that the system is in echelon form
+        // as soon as all the keys are added
+        let mut rows = vec![0u128; self.num_slots];
+        let mut results = vec![0u32; self.num_slots];
+        for &key_hash in key_hashes {
+            let (mut start, mut coefficients, mut result) = self.equation(key_hash);
+            loop {
+                if rows[start] == 0 {
+                    rows[start] = coefficients;
+                    results[start] = result;
+                    break;
+                }
+                coefficients ^= rows[start];
+                result ^= results[start];
+                if coefficients == 0 {
+                    if result == 0 {
+                        // the equation is a combination of the previous ones
+                        break;
+                    }
+                    return None;
+                }
+                let shift = coefficients.trailing_zeros();
+                start += shift as usize;
+                coefficients >>= shift;
+            }
+        }
+        Some((rows, results))
+    }
+
+    /// The number of random equations out of [`num_probes`] which are combinations of the rows of the system, and
+    /// thus satisfied by any solution.
+    fn implied_probes(&self, rows: &[u128]) -> u64 {
+        (0..num_probes(self.result_bits))
+            .filter(|&probe| {
+                // stands for the hash of a random item, away from the small values of trivial hashers
+                let (mut start, mut coefficients, _) = self.equation(hash::mix(probe, u64::MAX));
+                loop {
+                    if rows[start] == 0 {
+                        return false;
+                    }
+                    coefficients ^= rows[start];
+                    if coefficients == 0 {
+                        return true;
+                    }
+                    let shift = coefficients.trailing_zeros();
+                    start += shift as usize;
+                    coefficients >>= shift;
+                }
+            })
+            .count() as u64
+    }
+
+    /// Solve the system in echelon form given by `rows` and `results`.
+    fn back_substitute(&self, rows: &[u128], results: &[u32]) -> BitVec<u64, Lsb0> {
+        // from the last slot, whose value only depends on the slots after it
+        let mut solution = bitvec![u64, Lsb0; 0; self.num_slots * self.result_bits as usize];
+        for slot in (0..self.num_slots).rev() {
+            let free_slot = hash::mix(slot as u64, self.seed) as u32;
+            let band = (self.band_width as usize).min(self.num_slots - slot);
+            for bit in 0..self.result_bits as usize {
+                let column = bit * self.num_slots + slot;
+                let value = if rows[slot] == 0 {
+                    // no equation has its first coefficient here: the slot can take any value, which must be
+                    // random for the results of homogeneous filters to be random for other items
+                    free_slot >> bit & 1
+                } else {
+                    let slots: u128 = solution[column..column + band].load_le();
+                    (results[slot] >> bit & 1) ^ ((slots & rows[slot]).count_ones() % 2)
+                };
+                solution.set(column, value == 1);
+            }
+        }
+        solution
+    }
+
+    /// The start slot, the coefficients and the expected result of the equation of a key, given its hash.
+    fn equation(&self, key_hash: u64) -> (usize, u128, u32) {
+        let hash = hash::mix(key_hash, self.seed);
+        let num_starts = (self.num_slots - self.band_width as usize + 1) as u64;
+        let start = ((hash as u128 * num_starts as u128) >> 64) as usize;
+
+        let coefficients = (hash::mix(hash, 1) as u128) << 64 | hash::mix(hash, 2) as u128;
+        // the first coefficient is always set, so that the band of the equation really starts at `start`
+        let coefficients = (coefficients & (u128::MAX >> (128 - self.band_width))) | 1;
+
+        let result = match self.variant {
+            RibbonVariant::Standard => {
+                hash::mix(hash, 3) as u32 & (u32::MAX >> (32 - self.result_bits))
+            }
+            RibbonVariant::Homogeneous => 0,
+        };
+        (start, coefficients, result)
+    }
+}
+
+/// The number of slots of a filter of the given variant holding `len` keys with bands of `band_width` coefficients.
+fn num_slots(len: usize, band_width: u32, variant: RibbonVariant) -> usize {
+    // the equations of standard filters can be solved with high probability with about 0.6 * ln(len) / band_width
+    // more slots than keys, and at least one full band is needed
+    let band_width = band_width as f64;
+    let mut num_slots = len as f64 * (1.0 + 0.6 * (len.max(1) as f64).ln() / band_width);
+    if variant == RibbonVariant::Homogeneous {
+        // the equations of other items are rarely combinations of the equations of the keys with 4 / band_width
+        // more slots per key, and one more band for small filters
+        num_slots += len as f64 * 4.0 / band_width + band_width;
+    }
+    (num_slots.ceil() as usize).max(band_width as usize)
+}
+
+/// The number of random equations checked against the equations of the keys of a homogeneous filter with results
+/// of `result_bits` bits.
+fn num_probes(result_bits: u32) -> u64 {
+    (PROBES << result_bits.saturating_sub(9)).min(MAX_PROBES)
+}
+
+/// The smallest number of result bits reaching `fp_rate`.
+fn result_bits_for(fp_rate: f64) -> Result<u32, BloomError> {
+    // a lookup compares the result of an item with a single stored result
+    params::fingerprint_bits(fp_rate, 1, 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measured_fp_rate(ribbon: &RibbonFilter<u64>, len: u64) -> f64 {
+        let false_positives = (len..len + 200_000).filter(|i| ribbon.contains(i)).count();
+        false_positives as f64 / 200_000.0
+    }
+
+    #[test]
+    fn contains_all_keys() {
+        for variant in [RibbonVariant::Standard, RibbonVariant::Homogeneous] {
+            for band_width in [32, 64, 128] {
+                for len in [0, 1, 2, 10, 1000, 10_000] {
+                    let keys: Vec<u64> = (0..len).collect();
+                    let ribbon = RibbonFilter::try_with_options(
+                        &keys,
+                        8,
+                        band_width,
+                        variant,
+                        SeededState::with_seed(0),
+                    )
+                    .unwrap();
+                    assert_eq!(ribbon.len(), len as usize);
+                    assert!(
+                        keys.iter().all(|key| ribbon.contains(key)),
+                        "{variant:?} with {len} keys and bands of {band_width}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn one_percent_under_8_bits_per_key() {
+        for (len, seeds) in [(100, 6), (1000, 4), (10_000, 2), (100_000, 1)] {
+            let keys: Vec<u64> = (0..len).collect();
+            for variant in [RibbonVariant::Standard, RibbonVariant::Homogeneous] {
+                for seed in 0..seeds {
+                    let ribbon = RibbonFilter::try_with_options(
+                        &keys,
+                        7,
+                        DEFAULT_BAND_WIDTH,
+                        variant,
+                        SeededState::with_seed(seed),
+                    )
+                    .unwrap();
+                    // small filters still take a full band
+                    let bits_per_key = ribbon.num_bits() as f64 / keys.len() as f64;
+                    assert!(
+                        len < 10_000 || bits_per_key < 8.0,
+                        "{variant:?} with {len} keys: {bits_per_key} bits per key"
+                    );
+
+                    let measured = measured_fp_rate(&ribbon, len);
+                    assert!(
+                        (0.006..0.01).contains(&measured),
+                        "{variant:?} with {len} keys and seed {seed}: measured fp rate {measured}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn homogeneous_fp_rate_of_narrow_bands() {
+        // few keys and narrow bands make the equations of other items likely to be implied by the keys'
+        for (len, band_width) in [(30, 32), (100, 32), (100, 64), (1000, 32)] {
+            let keys: Vec<u64> = (0..len).collect();
+            for seed in 0..5 {
+                let ribbon = RibbonFilter::try_with_options(
+                    &keys,
+                    7,
+                    band_width,
+                    RibbonVariant::Homogeneous,
+                    SeededState::with_seed(seed),
+                )
+                .unwrap();
+                let measured = measured_fp_rate(&ribbon, len);
+                assert!(
+                    measured < 0.01,
+                    "{len} keys, bands of {band_width} and seed {seed}: measured fp rate {measured}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fp_rate_of_result_bits() {
+        let keys: Vec<u64> = (0..10_000).collect();
+        for result_bits in [1, 4, 8] {
+            let ribbon = RibbonFilter::try_with_options(
+                &keys,
+                result_bits,
+                128,
+                RibbonVariant::Standard,
+                SeededState::with_seed(0),
+            )
+            .unwrap();
+            let expected = 1.0 / (1u64 << result_bits) as f64;
+            let measured = measured_fp_rate(&ribbon, keys.len() as u64);
+            assert!(
+                (measured - expected).abs() < expected * 0.1,
+                "measured fp rate {measured} for {result_bits} result bits"
+            );
+        }
+    }
+
+    #[test]
+    fn homogeneous_many_result_bits() {
+        // too few probes to measure the fp rate of many result bits still tolerate one implied probe, rather than
+        // trying every seed
+        let keys: Vec<u64> = (0..1000).collect();
+        let first_seed = xor::construction_seeds().next();
+        for result_bits in [9, 10, 17, 18, 32] {
+            let ribbon = RibbonFilter::try_with_options(
+                &keys,
+                result_bits,
+                64,
+                RibbonVariant::Homogeneous,
+                SeededState::with_seed(0),
+            )
+            .unwrap();
+            assert_eq!(Some(ribbon.seed), first_seed, "{result_bits} result bits");
+            assert!(keys.iter().all(|key| ribbon.contains(key)));
+        }
+        assert_eq!(num_probes(9), PROBES);
+        assert_eq!(num_probes(17) >> (17 + 3), 1);
+        assert_eq!(num_probes(32), MAX_PROBES);
+    }
+
+    #[test]
+    fn duplicates_and_same_seed() {
+        let keys: Vec<u64> = (0..1000).chain(0..1000).collect();
+        let ribbon = RibbonFilter::<u64>::with_seed(&keys, 0.01, 42);
+        assert_eq!(ribbon.len(), 1000);
+        assert_eq!(ribbon.result_bits(), 7);
+        assert_eq!(ribbon.band_width(), 128);
+        assert_eq!(ribbon.variant(), RibbonVariant::Standard);
+        assert!(keys.iter().all(|key| ribbon.contains(key)));
+
+        let other = RibbonFilter::<u64>::with_seed(&keys, 0.01, 42);
+        assert_eq!(ribbon.solution, other.solution);
+    }
+
+    #[test]
+    fn try_with_options_rejects_invalid_parameters() {
+        let keys = [1u64, 2, 3];
+        let state = SeededState::with_seed(0);
+        let standard = RibbonVariant::Standard;
+        assert_eq!(
+            RibbonFilter::try_with_options(&keys, 0, 64, standard, state).err(),
+            Some(BloomError::InvalidFingerprintBits(0))
+        );
+        assert_eq!(
+            RibbonFilter::try_with_options(&keys, 33, 64, standard, state).err(),
+            Some(BloomError::InvalidFingerprintBits(33))
+        );
+        assert_eq!(
+            RibbonFilter::try_with_options(&keys, 8, 16, standard, state).err(),
+            Some(BloomError::InvalidBandWidth(16))
+        );
+        assert_eq!(result_bits_for(0.0), Err(BloomError::InvalidFpRate(0.0)));
+    }
+}
@@ -0,0 +1,363 @@
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+};
+
+use crate::{hash, params, BloomError, BloomParams, PartitionedBloomFilter, SeededState};
+
+/// Default growth factor of the capacity of successive sub-filters.
+const DEFAULT_GROWTH_FACTOR: f64 = 2.0;
+/// Default tightening ratio of the false positive rate of successive sub-filters.
+const DEFAULT_TIGHTENING_RATIO: f64 = 0.85;
+
+/// A bloom filter growing past its initial capacity while keeping its false positive rate bounded.
+///
+/// This is the scalable bloom filter of Almeida, Baquero, Preguiça and Hutchison (2007). Items are inserted in a
+/// chain of [`PartitionedBloomFilter`] sub-filters: once the last sub-filter reaches its capacity, a new one is appended, `growth_factor`
+/// times larger and with a false positive rate `tightening_ratio` times smaller. With a target false positive rate
+/// `P`, the `i`-th sub-filter is built with a rate of `P * (1 - r) * r^i`, so that the compound false positive rate
+/// of the chain, bounded by the sum of those rates, never exceeds `P` however many items are inserted.
+///
+/// Each sub-filter needs more bits per item than the previous one, so the filter can't grow forever: once the
+/// false positive rate of the next sub-filter underflows or its bitmap can't be addressed anymore,
+/// [`ScalableBloomFilter::insert`] returns [`BloomError::FilterFull`].
+///
+/// Example usage:
+/// ```
+/// use bloom_filter::ScalableBloomFilter;
+///
+/// let mut bloom = ScalableBloomFilter::new(10, 0.01);
+/// for i in 0..1000 {
+///     bloom.insert(&i).unwrap();
+/// }
+/// assert!(bloom.contains(&42));
+/// assert!(bloom.num_filters() > 1);
+/// ```
+pub struct ScalableBloomFilter<T: ?Sized, S = SeededState> {
+    filters: Vec<PartitionedBloomFilter<T, S>>,
+    initial_capacity: usize,
+    fp_rate: f64,
+    growth_factor: f64,
+    tightening_ratio: f64,
+    hash_builder: S,
+}
+
+impl<T: ?Sized + Hash> ScalableBloomFilter<T> {
+    /// Create a new ScalableBloomFilter with the default growth factor (2) and tightening ratio (0.85),
+    /// hashing items with a random seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`ScalableBloomFilter::try_new`] for a fallible version.
+    pub fn new(initial_capacity: usize, fp_rate: f64) -> Self {
+        params::expect_valid(Self::try_new(initial_capacity, fp_rate), "bloom filter")
+    }
+
+    /// Fallible version of [`ScalableBloomFilter::new`], see [`BloomFilter::try_new`](crate::BloomFilter::try_new) for the possible errors.
+    pub fn try_new(initial_capacity: usize, fp_rate: f64) -> Result<Self, BloomError> {
+        Self::try_with_options(
+            initial_capacity,
+            fp_rate,
+            DEFAULT_GROWTH_FACTOR,
+            DEFAULT_TIGHTENING_RATIO,
+            SeededState::new(),
+        )
+    }
+
+    /// Create a new ScalableBloomFilter with the default growth factor and tightening ratio, hashing items
+    /// deterministically from `seed`, see [`BloomFilter::with_seed`](crate::BloomFilter::with_seed).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [`ScalableBloomFilter::try_with_seed`] for a fallible version.
+    pub fn with_seed(initial_capacity: usize, fp_rate: f64, seed: u64) -> Self {
+        params::expect_valid(
+            Self::try_with_seed(initial_capacity, fp_rate, seed),
+            "bloom filter",
+        )
+    }
+
+    /// Fallible version of [`ScalableBloomFilter::with_seed`], see
+    /// [`BloomFilter::try_new`](crate::BloomFilter::try_new) for the possible errors.
+    pub fn try_with_seed(
+        initial_capacity: usize,
+        fp_rate: f64,
+        seed: u64,
+    ) -> Result<Self, BloomError> {
+        Self::try_with_options(
+            initial_capacity,
+            fp_rate,
+            DEFAULT_GROWTH_FACTOR,
+            DEFAULT_TIGHTENING_RATIO,
+            SeededState::with_seed(seed),
+        )
+    }
+}
+
+impl<T: ?Sized + Hash, S: BuildHasher + Clone + 'static> ScalableBloomFilter<T, S> {
+    /// Create a new ScalableBloomFilter with an explicit growth factor and tightening ratio, hashing items with
+    /// hashers built by `hash_builder`.
+    ///
+    /// Each sub-filter holds `growth_factor` times more items than the previous one, and has a false positive
+    /// rate `tightening_ratio` times smaller. Larger growth factors mean fewer sub-filters to probe on lookups,
+    /// and tightening ratios closer to 1 mean fewer bits per item for the first sub-filters but more for the
+    /// following ones.
+    ///
+    /// Returns an error if `growth_factor` is not greater than 1 or if `tightening_ratio` is not in the open
+    /// interval `(0, 1)`, see [`BloomFilter::try_new`](crate::BloomFilter::try_new) for the other possible errors.
+    pub fn try_with_options(
+        initial_capacity: usize,
+        fp_rate: f64,
+        growth_factor: f64,
+        tightening_ratio: f64,
+        hash_builder: S,
+    ) -> Result<Self, BloomError> {
+        params::check_fp_rate(fp_rate)?;
+        if !(growth_factor > 1.0 && growth_factor.is_finite()) {
+            return Err(BloomError::InvalidGrowthFactor(growth_factor));
+        }
+        if !params::is_in_unit_interval(tightening_ratio) {
+            return Err(BloomError::InvalidTighteningRatio(tightening_ratio));
+        }
+
+        let mut bloom = ScalableBloomFilter {
+            filters: Vec::new(),
+            initial_capacity,
+            fp_rate,
+            growth_factor,
+            tightening_ratio,
+            hash_builder,
+        };
+        let first = bloom.next_filter()?;
+        bloom.filters.push(first);
+        Ok(bloom)
+    }
+
+    /// Insert an element into the filter, growing it if needed.
+    ///
+    /// Elements already reported as present are not inserted again, so that duplicates don't fill the filter.
+    /// Returns [`BloomError::FilterFull`] and leaves the filter untouched if the filter needs to grow but its next
+    /// sub-filter can't be built.
+    pub fn insert(&mut self, item: &T) -> Result<(), BloomError> {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        if self.contains_hashes(h1, h2) {
+            return Ok(());
+        }
+
+        let last = self.filters.last().expect("there is always a sub-filter");
+        if last.inserted_count() >= last.capacity() as u64 {
+            let next = self.next_filter().map_err(|_| BloomError::FilterFull)?;
+            self.filters.push(next);
+        }
+        self.filters
+            .last_mut()
+            .expect("there is always a sub-filter")
+            .insert_hashes(h1, h2);
+        Ok(())
+    }
+
+    /// Checks if an element is contained in the filter, see [`BloomFilter::contains`](crate::BloomFilter::contains).
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let (h1, h2) = hash::hash_kernel(&self.hash_builder, item);
+        self.contains_hashes(h1, h2)
+    }
+
+    fn contains_hashes(&self, h1: u64, h2: u64) -> bool {
+        // the last sub-filters are the largest ones, so they are the most likely to hold the item
+        self.filters
+            .iter()
+            .rev()
+            .any(|filter| filter.contains_hashes(h1, h2))
+    }
+
+    /// Build the sub-filter following the current last one.
+    fn next_filter(&self) -> Result<PartitionedBloomFilter<T, S>, BloomError> {
+        let i = self.filters.len() as i32;
+        let capacity = (self.initial_capacity as f64 * self.growth_factor.powi(i)).ceil();
+        let fp_rate = self.fp_rate * (1.0 - self.tightening_ratio) * self.tightening_ratio.powi(i);
+        if capacity > usize::MAX as f64 {
+            return Err(BloomError::BitmapSizeOverflow);
+        }
+        let params = BloomParams::from_items_and_fp_rate(capacity as usize, fp_rate)?;
+        Ok(PartitionedBloomFilter::with_params(
+            params,
+            self.hash_builder.clone(),
+        ))
+    }
+}
+
+impl<T: ?Sized, S> ScalableBloomFilter<T, S> {
+    /// The number of sub-filters of this filter.
+    pub fn num_filters(&self) -> usize {
+        self.filters.len()
+    }
+
+    /// The number of items inserted in this filter, not counting elements which were already reported as
+    /// present when inserted.
+    pub fn len(&self) -> usize {
+        self.filters
+            .iter()
+            .map(|filter| filter.inserted_count() as usize)
+            .sum()
+    }
+
+    /// Whether no item was inserted in this filter.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of items this filter can hold before growing again.
+    pub fn capacity(&self) -> usize {
+        self.filters.iter().map(|filter| filter.capacity()).sum()
+    }
+
+    /// The number of bits of all the sub-filters of this filter.
+    pub fn num_bits(&self) -> usize {
+        self.filters.iter().map(|filter| filter.num_bits()).sum()
+    }
+
+    /// The expected false positive rate of this filter once all its sub-filters are full, which is below the
+    /// target false positive rate it was built with.
+    pub fn fp_rate(&self) -> f64 {
+        1.0 - self
+            .filters
+            .iter()
+            .map(|filter| {
+                1.0 - params::false_positive_rate(
+                    filter.capacity(),
+                    filter.num_bits(),
+                    filter.num_hashes(),
+                )
+            })
+            .product::<f64>()
+    }
+
+    /// The hash builder used by this filter.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_past_initial_capacity() {
+        let mut bloom = ScalableBloomFilter::with_seed(1000, 0.01, 0);
+        assert_eq!(bloom.num_filters(), 1);
+        for i in 0..100_000u32 {
+            bloom.insert(&i).unwrap();
+        }
+        for i in 0..100_000u32 {
+            assert!(bloom.contains(&i));
+        }
+
+        // 1000 * (1 + 2 + ... + 2^6) = 127_000 is the first capacity above 100_000
+        assert_eq!(bloom.num_filters(), 7);
+        assert!(bloom.capacity() >= 100_000);
+        assert!(bloom.fp_rate() <= 0.01);
+
+        let false_positives = (100_000..300_000u32).filter(|i| bloom.contains(i)).count();
+        let measured = false_positives as f64 / 200_000.0;
+        assert!(measured < 0.01, "measured fp rate {measured}");
+    }
+
+    #[test]
+    fn duplicates_are_not_counted() {
+        let mut bloom = ScalableBloomFilter::with_seed(10, 0.01, 0);
+        assert!(bloom.is_empty());
+        for _ in 0..100 {
+            bloom.insert("item").unwrap();
+        }
+        assert_eq!(bloom.len(), 1);
+        assert_eq!(bloom.num_filters(), 1);
+    }
+
+    #[test]
+    fn custom_growth() {
+        let mut bloom =
+            ScalableBloomFilter::try_with_options(100, 0.001, 4.0, 0.5, SeededState::with_seed(0))
+                .unwrap();
+        for i in 0..10_000u32 {
+            bloom.insert(&i).unwrap();
+        }
+        // 100 * (1 + 4 + 16 + 64) = 8_500 is the last capacity below 10_000
+        assert_eq!(bloom.num_filters(), 5);
+        assert!(bloom.fp_rate() <= 0.001);
+        assert!((0..10_000u32).all(|i| bloom.contains(&i)));
+    }
+
+    #[test]
+    fn small_initial_capacity() {
+        for (initial_capacity, tightening_ratio) in [(1, 0.9), (10, 0.8), (10, 0.9)] {
+            let mut bloom = ScalableBloomFilter::try_with_options(
+                initial_capacity,
+                0.01,
+                2.0,
+                tightening_ratio,
+                SeededState::with_seed(0),
+            )
+            .unwrap();
+            for i in 0..10_000u32 {
+                bloom.insert(&i).unwrap();
+            }
+            assert!(bloom.len() > 9_900);
+            assert!(bloom.fp_rate() <= 0.01);
+
+            let false_positives = (10_000..210_000u32).filter(|i| bloom.contains(i)).count();
+            let measured = false_positives as f64 / 200_000.0;
+            assert!(measured < 0.01, "measured fp rate {measured}");
+        }
+    }
+
+    #[test]
+    fn full() {
+        // the false positive rate of the sub-filters underflows long before their size overflows
+        let mut bloom =
+            ScalableBloomFilter::try_with_options(1, 0.01, 1.0001, 0.1, SeededState::with_seed(0))
+                .unwrap();
+        let full = (0..1000u32).find(|i| bloom.insert(i).is_err()).unwrap();
+        assert_eq!(bloom.insert(&full), Err(BloomError::FilterFull));
+        assert!((0..full).all(|i| bloom.contains(&i)));
+        assert!(!bloom.contains(&full));
+    }
+
+    #[test]
+    fn try_with_options_rejects_invalid_parameters() {
+        let state = SeededState::with_seed(0);
+        assert_eq!(
+            ScalableBloomFilter::<str>::try_with_options(100, 0.01, 0.5, 0.8, state).err(),
+            Some(BloomError::InvalidGrowthFactor(0.5))
+        );
+        assert_eq!(
+            ScalableBloomFilter::<str>::try_with_options(100, 0.01, 1.0, 0.8, state).err(),
+            Some(BloomError::InvalidGrowthFactor(1.0))
+        );
+        assert_eq!(
+            ScalableBloomFilter::<str>::try_with_options(100, 0.01, 2.0, 1.0, state).err(),
+            Some(BloomError::InvalidTighteningRatio(1.0))
+        );
+        assert_eq!(
+            ScalableBloomFilter::<str>::try_with_options(0, 0.01, 2.0, 0.8, state).err(),
+            Some(BloomError::ZeroItemsCount)
+        );
+        assert_eq!(
+            ScalableBloomFilter::<str>::try_with_seed(0, 0.01, 0).err(),
+            Some(BloomError::ZeroItemsCount)
+        );
+        for fp_rate in [0.0, 1.0, 1.5] {
+            assert_eq!(
+                ScalableBloomFilter::<str>::try_with_options(100, fp_rate, 2.0, 0.8, state).err(),
+                Some(BloomError::InvalidFpRate(fp_rate))
+            );
+        }
+        assert!(matches!(
+            ScalableBloomFilter::<str>::try_with_options(100, f64::NAN, 2.0, 0.8, state),
+            Err(BloomError::InvalidFpRate(fp_rate)) if fp_rate.is_nan()
+        ));
+    }
+}
@@ -0,0 +1,530 @@
+use std::{
+    fmt,
+    io::{self, Read, Write},
+    marker::PhantomData,
+};
+
+use bitvec::prelude::*;
+
+use crate::{BloomError, BloomFilter, SeededState};
+
+/// Magic bytes opening every serialized filter.
+const MAGIC: [u8; 4] = *b"BLMF";
+/// Version of the binary format, bumped on every incompatible change.
+pub(crate) const VERSION: u8 = 1;
+/// Identifier of the hashing scheme of [`SeededState`]: 128-bit SipHash-1-3 keyed from the seed, with
+/// `(h1, h2) = H(item)` and `index_i = (h1 + i * h2 + (i^3 - i) / 6) mod m`.
+pub(crate) const HASH_SIPHASH_1_3: u8 = 1;
+/// Size of the header: magic, version, hash algorithm, seed, m, k, capacity and inserted count.
+const HEADER_LEN: usize = 4 + 1 + 1 + 8 + 8 + 4 + 8 + 8;
+/// Number of bitmap words buffered at once when streaming a filter.
+pub(crate) const CHUNK_WORDS: usize = 1024;
+
+/// Errors returned when decoding a serialized bloom filter.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The underlying reader failed.
+    Io(io::Error),
+    /// The payload ended before the whole filter could be read.
+    Truncated,
+    /// The payload does not start with the magic bytes of the format.
+    InvalidMagic,
+    /// The payload was written by an unknown version of the format.
+    UnsupportedVersion(u8),
+    /// The payload was hashed with an unknown hashing scheme.
+    UnsupportedHashAlgorithm(u8),
+    /// The parameters of the payload do not describe a valid filter.
+    InvalidParameters(BloomError),
+    /// The number of bitmap words does not match the number of bits of the filter.
+    InvalidBitmapLength { expected: usize, actual: usize },
+    /// Bits past the end of the bitmap are set.
+    NonZeroPadding,
+    /// The checksum of the payload does not match its content.
+    ChecksumMismatch { expected: u32, computed: u32 },
+    /// Bytes remain after the end of the filter.
+    TrailingBytes,
+    /// The header of the payload is malformed or not supported.
+    InvalidHeader(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Io(err) => write!(f, "failed to read bloom filter: {err}"),
+            DecodeError::Truncated => write!(f, "bloom filter payload is truncated"),
+            DecodeError::InvalidMagic => write!(f, "payload is not a serialized bloom filter"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported bloom filter format version {version}")
+            }
+            DecodeError::UnsupportedHashAlgorithm(id) => {
+                write!(f, "unsupported bloom filter hash algorithm {id}")
+            }
+            DecodeError::InvalidParameters(err) => {
+                write!(f, "invalid bloom filter parameters: {err}")
+            }
+            DecodeError::InvalidBitmapLength { expected, actual } => {
+                write!(f, "expected {expected} bitmap words, got {actual}")
+            }
+            DecodeError::NonZeroPadding => write!(f, "bits are set past the end of the bitmap"),
+            DecodeError::ChecksumMismatch { expected, computed } => write!(
+                f,
+                "checksum mismatch: expected {expected:#010x}, computed {computed:#010x}"
+            ),
+            DecodeError::TrailingBytes => write!(f, "trailing bytes after the bloom filter"),
+            DecodeError::InvalidHeader(reason) => {
+                write!(f, "invalid bloom filter header: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Io(err) => Some(err),
+            DecodeError::InvalidParameters(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for DecodeError {
+    fn from(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            DecodeError::Truncated
+        } else {
+            DecodeError::Io(err)
+        }
+    }
+}
+
+impl<T: ?Sized> BloomFilter<T, SeededState> {
+    /// Serialize the filter into a versioned binary payload.
+    ///
+    /// The payload is made of, with all integers in little endian:
+    /// - the magic bytes `BLMF`,
+    /// - the format version as a `u8`, currently 1,
+    /// - the hash algorithm identifier as a `u8`, 1 for the SipHash-1-3 scheme of [`SeededState`],
+    /// - the seed as a `u64`,
+    /// - the number of bits `m` as a `u64`,
+    /// - the number of hash functions `k` as a `u32`,
+    /// - the capacity as a `u64`,
+    /// - the inserted count as a `u64`,
+    /// - the bitmap as `ceil(m / 64)` `u64` words, bit `i` being bit `i % 64` of word `i / 64`,
+    /// - the CRC32 (IEEE) of all the previous bytes as a `u32`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + 8 * self.bitmap.as_raw_slice().len() + 4);
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec never fails");
+        bytes
+    }
+
+    /// Deserialize a filter from a payload produced by [`BloomFilter::to_bytes`].
+    ///
+    /// Returns an error if the payload is corrupt, truncated, followed by extra bytes, or was produced by an
+    /// incompatible version of the format.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DecodeError> {
+        let bloom = Self::read_from(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        Ok(bloom)
+    }
+
+    /// Serialize the filter into `writer`, see [`BloomFilter::to_bytes`] for the format.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = Crc32Writer {
+            inner: writer,
+            hasher: crc32fast::Hasher::new(),
+        };
+
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&MAGIC);
+        header.push(VERSION);
+        header.push(HASH_SIPHASH_1_3);
+        header.extend_from_slice(&self.hash_builder.seed().to_le_bytes());
+        header.extend_from_slice(&self.optimal_m.to_le_bytes());
+        header.extend_from_slice(&self.optimal_k.to_le_bytes());
+        header.extend_from_slice(&(self.capacity as u64).to_le_bytes());
+        header.extend_from_slice(&self.inserted_count.to_le_bytes());
+        writer.write_all(&header)?;
+
+        let mut buffer = Vec::with_capacity(8 * CHUNK_WORDS);
+        for chunk in self.bitmap.as_raw_slice().chunks(CHUNK_WORDS) {
+            buffer.clear();
+            for word in chunk {
+                buffer.extend_from_slice(&word.to_le_bytes());
+            }
+            writer.write_all(&buffer)?;
+        }
+
+        let checksum = writer.hasher.finalize();
+        writer.inner.write_all(&checksum.to_le_bytes())
+    }
+
+    /// Deserialize a filter from `reader`, see [`BloomFilter::from_bytes`].
+    ///
+    /// The reader is left positioned right after the filter.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, DecodeError> {
+        let mut reader = Crc32Reader {
+            inner: reader,
+            hasher: crc32fast::Hasher::new(),
+        };
+
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        let (magic, rest) = header.split_at(4);
+        if magic != MAGIC {
+            return Err(DecodeError::InvalidMagic);
+        }
+        if rest[0] != VERSION {
+            return Err(DecodeError::UnsupportedVersion(rest[0]));
+        }
+        if rest[1] != HASH_SIPHASH_1_3 {
+            return Err(DecodeError::UnsupportedHashAlgorithm(rest[1]));
+        }
+        let seed = u64::from_le_bytes(rest[2..10].try_into().unwrap());
+        let num_bits = u64::from_le_bytes(rest[10..18].try_into().unwrap());
+        let num_hashes = u32::from_le_bytes(rest[18..22].try_into().unwrap());
+        let capacity = u64::from_le_bytes(rest[22..30].try_into().unwrap());
+        let inserted_count = u64::from_le_bytes(rest[30..38].try_into().unwrap());
+
+        let words_count = words_count(num_bits)?;
+        // don't trust the header for the allocation, a corrupt payload would otherwise abort the process
+        let mut words = Vec::with_capacity(words_count.min(CHUNK_WORDS));
+        let mut buffer = vec![0; 8 * CHUNK_WORDS];
+        while words.len() < words_count {
+            let chunk = &mut buffer[..8 * (words_count - words.len()).min(CHUNK_WORDS)];
+            reader.read_exact(chunk)?;
+            words.extend(
+                chunk
+                    .chunks_exact(8)
+                    .map(|word| u64::from_le_bytes(word.try_into().unwrap())),
+            );
+        }
+
+        let computed = reader.hasher.finalize();
+        let mut checksum = [0; 4];
+        reader.inner.read_exact(&mut checksum)?;
+        let expected = u32::from_le_bytes(checksum);
+        if expected != computed {
+            return Err(DecodeError::ChecksumMismatch { expected, computed });
+        }
+
+        Self::from_raw_parts(
+            num_bits,
+            num_hashes,
+            capacity,
+            inserted_count,
+            SeededState::with_seed(seed),
+            words,
+        )
+    }
+}
+
+impl<T: ?Sized, S> BloomFilter<T, S> {
+    /// Rebuild a filter from its serialized fields, checking they describe a valid filter.
+    fn from_raw_parts(
+        num_bits: u64,
+        num_hashes: u32,
+        capacity: u64,
+        inserted_count: u64,
+        hash_builder: S,
+        words: Vec<u64>,
+    ) -> Result<Self, DecodeError> {
+        if num_hashes == 0 {
+            return Err(DecodeError::InvalidParameters(BloomError::ZeroHashesCount));
+        }
+        let expected = words_count(num_bits)?;
+        if words.len() != expected {
+            return Err(DecodeError::InvalidBitmapLength {
+                expected,
+                actual: words.len(),
+            });
+        }
+
+        let mut bitmap = BitVec::<u64, Lsb0>::from_vec(words);
+        if bitmap[num_bits as usize..].any() {
+            return Err(DecodeError::NonZeroPadding);
+        }
+        bitmap.truncate(num_bits as usize);
+
+        Ok(BloomFilter {
+            bitmap,
+            optimal_m: num_bits,
+            optimal_k: num_hashes,
+            capacity: usize::try_from(capacity).unwrap_or(usize::MAX),
+            inserted_count,
+            hash_builder,
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// The number of words backing a bitmap of `num_bits` bits, checking it can be allocated.
+fn words_count(num_bits: u64) -> Result<usize, DecodeError> {
+    if num_bits == 0 {
+        return Err(DecodeError::InvalidParameters(BloomError::ZeroBitsCount));
+    }
+    match usize::try_from(num_bits) {
+        Ok(num_bits) if num_bits <= BitSlice::<u64, Lsb0>::MAX_BITS => {
+            Ok(num_bits.div_ceil(u64::BITS as usize))
+        }
+        _ => Err(DecodeError::InvalidParameters(
+            BloomError::BitmapSizeOverflow,
+        )),
+    }
+}
+
+/// A writer computing the CRC32 of everything written through it.
+pub(crate) struct Crc32Writer<W> {
+    pub(crate) inner: W,
+    pub(crate) hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> Write for Crc32Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader computing the CRC32 of everything read through it.
+pub(crate) struct Crc32Reader<R> {
+    pub(crate) inner: R,
+    pub(crate) hasher: crc32fast::Hasher,
+}
+
+impl<R: Read> Read for Crc32Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::{BloomFilter, SeededState};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "SeededState")]
+    struct SeededStateRepr(u64);
+
+    impl Serialize for SeededState {
+        fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+            SeededStateRepr(self.seed()).serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for SeededState {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            SeededStateRepr::deserialize(deserializer).map(|repr| SeededState::with_seed(repr.0))
+        }
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename = "BloomFilter")]
+    struct BloomFilterRef<'a, S> {
+        num_bits: u64,
+        num_hashes: u32,
+        capacity: u64,
+        inserted_count: u64,
+        hash_builder: &'a S,
+        words: &'a [u64],
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "BloomFilter")]
+    struct BloomFilterRepr<S> {
+        num_bits: u64,
+        num_hashes: u32,
+        capacity: u64,
+        inserted_count: u64,
+        hash_builder: S,
+        words: Vec<u64>,
+    }
+
+    impl<T: ?Sized, S: Serialize> Serialize for BloomFilter<T, S> {
+        fn serialize<Se: Serializer>(&self, serializer: Se) -> Result<Se::Ok, Se::Error> {
+            BloomFilterRef {
+                num_bits: self.optimal_m,
+                num_hashes: self.optimal_k,
+                capacity: self.capacity as u64,
+                inserted_count: self.inserted_count,
+                hash_builder: &self.hash_builder,
+                words: self.bitmap.as_raw_slice(),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T: ?Sized, S: Deserialize<'de>> Deserialize<'de> for BloomFilter<T, S> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let repr = BloomFilterRepr::deserialize(deserializer)?;
+            BloomFilter::from_raw_parts(
+                repr.num_bits,
+                repr.num_hashes,
+                repr.capacity,
+                repr.inserted_count,
+                repr.hash_builder,
+                repr.words,
+            )
+            .map_err(de::Error::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter() -> BloomFilter<str> {
+        let mut bloom = BloomFilter::with_seed(100, 0.01, 42);
+        for item in ["a", "b", "c"] {
+            bloom.insert(item);
+        }
+        bloom
+    }
+
+    #[test]
+    fn round_trip() {
+        let bloom = filter();
+        let bytes = bloom.to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN + 8 * 15 + 4);
+
+        let decoded = BloomFilter::<str>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.bitmap, bloom.bitmap);
+        assert_eq!(decoded.seed(), 42);
+        assert_eq!(decoded.num_bits(), bloom.num_bits());
+        assert_eq!(decoded.num_hashes(), bloom.num_hashes());
+        assert_eq!(decoded.capacity(), 100);
+        assert_eq!(decoded.inserted_count(), 3);
+        for item in ["a", "b", "c"] {
+            assert!(decoded.contains(item));
+        }
+    }
+
+    #[test]
+    fn stream_round_trip() {
+        let bloom = filter();
+        let mut bytes = Vec::new();
+        bloom.write_to(&mut bytes).unwrap();
+        bytes.extend_from_slice(b"next");
+
+        let mut reader = bytes.as_slice();
+        let decoded = BloomFilter::<str>::read_from(&mut reader).unwrap();
+        assert_eq!(decoded.bitmap, bloom.bitmap);
+        assert_eq!(reader, b"next");
+    }
+
+    #[test]
+    fn golden_header() {
+        let bytes = filter().to_bytes();
+        assert_eq!(&bytes[..4], b"BLMF");
+        assert_eq!(&bytes[4..6], &[VERSION, HASH_SIPHASH_1_3]);
+        assert_eq!(&bytes[6..14], &42u64.to_le_bytes());
+        assert_eq!(&bytes[14..22], &960u64.to_le_bytes());
+        assert_eq!(&bytes[22..26], &7u32.to_le_bytes());
+        assert_eq!(&bytes[26..34], &100u64.to_le_bytes());
+        assert_eq!(&bytes[34..42], &3u64.to_le_bytes());
+    }
+
+    #[test]
+    fn rejects_corrupt_payloads() {
+        let bytes = filter().to_bytes();
+
+        let mut corrupt = bytes.clone();
+        corrupt[0] = b'X';
+        assert!(matches!(
+            BloomFilter::<str>::from_bytes(&corrupt),
+            Err(DecodeError::InvalidMagic)
+        ));
+
+        let mut corrupt = bytes.clone();
+        corrupt[4] = 2;
+        assert!(matches!(
+            BloomFilter::<str>::from_bytes(&corrupt),
+            Err(DecodeError::UnsupportedVersion(2))
+        ));
+
+        let mut corrupt = bytes.clone();
+        corrupt[5] = 7;
+        assert!(matches!(
+            BloomFilter::<str>::from_bytes(&corrupt),
+            Err(DecodeError::UnsupportedHashAlgorithm(7))
+        ));
+
+        let mut corrupt = bytes.clone();
+        corrupt[HEADER_LEN + 3] ^= 0x10;
+        assert!(matches!(
+            BloomFilter::<str>::from_bytes(&corrupt),
+            Err(DecodeError::ChecksumMismatch { .. })
+        ));
+
+        let mut corrupt = bytes.clone();
+        corrupt[14..22].copy_from_slice(&0u64.to_le_bytes());
+        assert!(matches!(
+            BloomFilter::<str>::from_bytes(&corrupt),
+            Err(DecodeError::InvalidParameters(BloomError::ZeroBitsCount))
+        ));
+
+        // a huge bit count must not be trusted for the allocation
+        let mut corrupt = bytes.clone();
+        corrupt[14..22].copy_from_slice(&(1u64 << 60).to_le_bytes());
+        assert!(matches!(
+            BloomFilter::<str>::from_bytes(&corrupt),
+            Err(DecodeError::Truncated)
+        ));
+
+        assert!(matches!(
+            BloomFilter::<str>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::Truncated)
+        ));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            BloomFilter::<str>::from_bytes(&trailing),
+            Err(DecodeError::TrailingBytes)
+        ));
+    }
+
+    #[test]
+    fn rejects_non_zero_padding() {
+        let bloom = BloomFilter::<str>::with_bits_and_hashes(100, 3);
+        let mut bytes = bloom.to_bytes();
+        let last_word = HEADER_LEN + 8;
+        bytes[last_word + 7] = 0x80;
+        let checksum = crc32fast::hash(&bytes[..bytes.len() - 4]);
+        let checksum_start = bytes.len() - 4;
+        bytes[checksum_start..].copy_from_slice(&checksum.to_le_bytes());
+        assert!(matches!(
+            BloomFilter::<str>::from_bytes(&bytes),
+            Err(DecodeError::NonZeroPadding)
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let bloom = filter();
+        let json = serde_json::to_string(&bloom).unwrap();
+        let decoded: BloomFilter<str> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.bitmap, bloom.bitmap);
+        assert_eq!(decoded.seed(), 42);
+        assert_eq!(decoded.inserted_count(), 3);
+        assert!(decoded.contains("a"));
+
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["words"].as_array_mut().unwrap().pop();
+        assert!(serde_json::from_value::<BloomFilter<str>>(value).is_err());
+    }
+}
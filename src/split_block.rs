@@ -0,0 +1,533 @@
+use std::io::{self, Read, Write};
+
+use xxhash_rust::xxh64::xxh64;
+
+use crate::{params, BloomError, DecodeError};
+
+/// Number of 32-bit words in a block.
+const BLOCK_WORDS: usize = 8;
+/// Number of bytes in a block.
+const BLOCK_BYTES: usize = BLOCK_WORDS * 4;
+/// Largest filter written by the reference implementation, 128 MiB.
+const MAX_BYTES: usize = 128 * 1024 * 1024;
+/// Odd constants used to derive the eight bits set in a block from a 32-bit hash.
+const SALT: [u32; BLOCK_WORDS] = [
+    0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d, 0x705495c7, 0x2df1424b, 0x9efc4947, 0x5c6bfb31,
+];
+
+/// Thrift compact protocol type identifiers.
+const THRIFT_STOP: u8 = 0;
+const THRIFT_BOOL_TRUE: u8 = 1;
+const THRIFT_BOOL_FALSE: u8 = 2;
+const THRIFT_BYTE: u8 = 3;
+const THRIFT_I16: u8 = 4;
+const THRIFT_I32: u8 = 5;
+const THRIFT_I64: u8 = 6;
+const THRIFT_DOUBLE: u8 = 7;
+const THRIFT_BINARY: u8 = 8;
+const THRIFT_LIST: u8 = 9;
+const THRIFT_SET: u8 = 10;
+const THRIFT_MAP: u8 = 11;
+const THRIFT_STRUCT: u8 = 12;
+/// Nesting depth past which a Thrift payload is considered malicious.
+const THRIFT_MAX_DEPTH: usize = 32;
+
+/// A value which can be inserted in a [`SplitBlockBloomFilter`].
+///
+/// Values are hashed with xxHash64 (seed 0) over their Parquet plain encoding: little endian integers and floats,
+/// and the raw bytes, without length prefix, of byte arrays and strings.
+pub trait SbbfValue {
+    /// The xxHash64 of the plain encoding of the value.
+    fn sbbf_hash(&self) -> u64;
+}
+
+macro_rules! impl_sbbf_value_le {
+    ($($ty:ty),*) => {
+        $(
+            impl SbbfValue for $ty {
+                fn sbbf_hash(&self) -> u64 {
+                    xxh64(&self.to_le_bytes(), 0)
+                }
+            }
+        )*
+    };
+}
+
+// INT32, INT64, FLOAT and DOUBLE physical types, unsigned integers share the encoding of signed ones
+impl_sbbf_value_le!(i32, u32, i64, u64, f32, f64);
+
+// BYTE_ARRAY and FIXED_LEN_BYTE_ARRAY physical types
+impl SbbfValue for [u8] {
+    fn sbbf_hash(&self) -> u64 {
+        xxh64(self, 0)
+    }
+}
+
+impl SbbfValue for str {
+    fn sbbf_hash(&self) -> u64 {
+        xxh64(self.as_bytes(), 0)
+    }
+}
+
+/// The Split Block Bloom Filter of the Apache Parquet format.
+///
+/// This implements the bloom filters stored in Parquet files, bit for bit: the filter is made of 256-bit blocks
+/// of eight 32-bit words. The upper 32 bits of the 64-bit hash of a value select its block, and its lower 32 bits
+/// are multiplied by eight salt constants to set one bit in each word of the block. Values are hashed with
+/// xxHash64 over their plain encoding, see [`SbbfValue`].
+///
+/// [`SplitBlockBloomFilter::to_bytes`] and [`SplitBlockBloomFilter::from_bytes`] read and write the filter as
+/// stored in Parquet files, i.e. a Thrift `BloomFilterHeader` followed by the bitset.
+///
+/// Example usage:
+/// ```
+/// use bloom_filter::SplitBlockBloomFilter;
+///
+/// let mut bloom = SplitBlockBloomFilter::with_ndv_and_fpp(100, 0.01).unwrap();
+/// bloom.insert("item");
+/// assert!(bloom.contains("item"));
+///
+/// let bytes = bloom.to_bytes();
+/// assert!(SplitBlockBloomFilter::from_bytes(&bytes).unwrap().contains("item"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitBlockBloomFilter {
+    blocks: Vec<[u32; BLOCK_WORDS]>,
+}
+
+impl SplitBlockBloomFilter {
+    /// Create a new empty filter of `num_bytes` bytes.
+    ///
+    /// Returns an error if `num_bytes` is not a positive multiple of 32 no larger than 128 MiB.
+    pub fn new(num_bytes: usize) -> Result<Self, BloomError> {
+        if num_bytes == 0 || num_bytes % BLOCK_BYTES != 0 || num_bytes > MAX_BYTES {
+            return Err(BloomError::InvalidNumBytes(num_bytes));
+        }
+        Ok(SplitBlockBloomFilter {
+            blocks: vec![[0; BLOCK_WORDS]; num_bytes / BLOCK_BYTES],
+        })
+    }
+
+    /// Create a new empty filter sized for `ndv` distinct values with a false positive rate of `fpp`.
+    ///
+    /// The size is rounded up to a power of two between 32 bytes and 128 MiB, as done by the reference
+    /// implementation. Returns an error if `ndv` is zero or `fpp` is not in the open interval `(0, 1)`.
+    pub fn with_ndv_and_fpp(ndv: u64, fpp: f64) -> Result<Self, BloomError> {
+        if ndv == 0 {
+            return Err(BloomError::ZeroItemsCount);
+        }
+        params::check_fp_rate(fpp)?;
+        // each value sets 8 bits, so m = -8 * n / ln(1 - p^(1/8))
+        let num_bits = -8.0 * ndv as f64 / (-fpp.powf(1.0 / 8.0)).ln_1p();
+        let num_bytes = if num_bits / 8.0 >= MAX_BYTES as f64 {
+            MAX_BYTES
+        } else {
+            ((num_bits / 8.0).ceil() as usize)
+                .next_power_of_two()
+                .clamp(BLOCK_BYTES, MAX_BYTES)
+        };
+        Self::new(num_bytes)
+    }
+
+    /// The number of bytes of the bitset of this filter.
+    pub fn num_bytes(&self) -> usize {
+        self.blocks.len() * BLOCK_BYTES
+    }
+
+    /// Insert a value into the filter.
+    pub fn insert<V: SbbfValue + ?Sized>(&mut self, value: &V) {
+        self.insert_hash(value.sbbf_hash());
+    }
+
+    /// Checks if a value is contained in the filter, see [`BloomFilter::contains`](crate::BloomFilter::contains).
+    pub fn contains<V: SbbfValue + ?Sized>(&self, value: &V) -> bool {
+        self.check_hash(value.sbbf_hash())
+    }
+
+    /// Insert a value given its 64-bit hash.
+    pub fn insert_hash(&mut self, hash: u64) {
+        let index = self.block_index(hash);
+        let mask = Self::mask(hash as u32);
+        for (word, mask) in self.blocks[index].iter_mut().zip(mask) {
+            *word |= mask;
+        }
+    }
+
+    /// Checks if a value is contained in the filter given its 64-bit hash.
+    pub fn check_hash(&self, hash: u64) -> bool {
+        let mask = Self::mask(hash as u32);
+        self.blocks[self.block_index(hash)]
+            .iter()
+            .zip(mask)
+            .all(|(word, mask)| word & mask != 0)
+    }
+
+    fn block_index(&self, hash: u64) -> usize {
+        (((hash >> 32) * self.blocks.len() as u64) >> 32) as usize
+    }
+
+    fn mask(hash: u32) -> [u32; BLOCK_WORDS] {
+        SALT.map(|salt| 1 << (hash.wrapping_mul(salt) >> 27))
+    }
+
+    /// Serialize the filter as stored in Parquet files: a Thrift compact `BloomFilterHeader` declaring the
+    /// `BLOCK` algorithm, the `XXHASH` hash and no compression, followed by the bitset as little endian words.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + self.num_bytes());
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec never fails");
+        bytes
+    }
+
+    /// Deserialize a filter stored in a Parquet file, see [`SplitBlockBloomFilter::to_bytes`].
+    ///
+    /// Returns an error if the payload is truncated, followed by extra bytes, or if its header is malformed or
+    /// declares an unsupported algorithm, hash or compression.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DecodeError> {
+        let bloom = Self::read_from(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        Ok(bloom)
+    }
+
+    /// Serialize the filter into `writer`, see [`SplitBlockBloomFilter::to_bytes`].
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&header(self.num_bytes() as i32))?;
+        let mut buffer = Vec::with_capacity(BLOCK_BYTES * 1024);
+        for chunk in self.blocks.chunks(1024) {
+            buffer.clear();
+            for word in chunk.iter().flatten() {
+                buffer.extend_from_slice(&word.to_le_bytes());
+            }
+            writer.write_all(&buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Deserialize a filter from `reader`, see [`SplitBlockBloomFilter::from_bytes`].
+    ///
+    /// The reader is left positioned right after the bitset.
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Self, DecodeError> {
+        let num_bytes = read_header(&mut reader)?;
+        let num_bytes = usize::try_from(num_bytes)
+            .map_err(|_| DecodeError::InvalidHeader("negative number of bytes"))?;
+        let mut bloom = Self::new(num_bytes).map_err(DecodeError::InvalidParameters)?;
+
+        let mut buffer = [0; BLOCK_BYTES];
+        for block in &mut bloom.blocks {
+            reader.read_exact(&mut buffer)?;
+            for (word, bytes) in block.iter_mut().zip(buffer.chunks_exact(4)) {
+                *word = u32::from_le_bytes(bytes.try_into().unwrap());
+            }
+        }
+        Ok(bloom)
+    }
+}
+
+/// The Thrift compact encoding of a `BloomFilterHeader` for a bitset of `num_bytes` bytes.
+fn header(num_bytes: i32) -> Vec<u8> {
+    let mut header = vec![(1 << 4) | THRIFT_I32];
+    let mut value = ((num_bytes << 1) ^ (num_bytes >> 31)) as u32;
+    while value >= 0x80 {
+        header.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    header.push(value as u8);
+    // algorithm, hash and compression are unions whose first member is an empty struct
+    for _ in 0..3 {
+        header.extend_from_slice(&[
+            (1 << 4) | THRIFT_STRUCT,
+            (1 << 4) | THRIFT_STRUCT,
+            THRIFT_STOP,
+            THRIFT_STOP,
+        ]);
+    }
+    header.push(THRIFT_STOP);
+    header
+}
+
+/// Read a Thrift compact `BloomFilterHeader`, returning its number of bytes.
+fn read_header<R: Read>(reader: &mut R) -> Result<i32, DecodeError> {
+    let mut num_bytes = None;
+    let (mut algorithm, mut hash, mut compression) = (None, None, None);
+
+    let mut last_id = 0;
+    while let Some((field_type, id)) = read_field_header(reader, &mut last_id)? {
+        match (id, field_type) {
+            (1, THRIFT_I32) => num_bytes = Some(read_varint(reader)?),
+            (2, THRIFT_STRUCT) => algorithm = read_union(reader)?,
+            (3, THRIFT_STRUCT) => hash = read_union(reader)?,
+            (4, THRIFT_STRUCT) => compression = read_union(reader)?,
+            _ => skip(reader, field_type, 0)?,
+        }
+    }
+
+    let num_bytes = num_bytes.ok_or(DecodeError::InvalidHeader("missing number of bytes"))?;
+    // the first member of each union is the only one defined by the specification
+    if algorithm != Some(1) {
+        return Err(DecodeError::InvalidHeader("unsupported algorithm"));
+    }
+    if hash != Some(1) {
+        return Err(DecodeError::InvalidHeader("unsupported hash"));
+    }
+    if compression != Some(1) {
+        return Err(DecodeError::InvalidHeader("unsupported compression"));
+    }
+    let num_bytes = num_bytes as u32;
+    Ok(((num_bytes >> 1) as i32) ^ -((num_bytes & 1) as i32))
+}
+
+/// Read a union, returning the identifier of its member.
+fn read_union<R: Read>(reader: &mut R) -> Result<Option<i16>, DecodeError> {
+    let mut member = None;
+    let mut last_id = 0;
+    while let Some((field_type, id)) = read_field_header(reader, &mut last_id)? {
+        member = member.or(Some(id));
+        skip(reader, field_type, 1)?;
+    }
+    Ok(member)
+}
+
+/// Read the header of the next field of a struct, returning `None` at the end of the struct.
+fn read_field_header<R: Read>(
+    reader: &mut R,
+    last_id: &mut i16,
+) -> Result<Option<(u8, i16)>, DecodeError> {
+    let byte = read_byte(reader)?;
+    let field_type = byte & 0x0f;
+    if field_type == THRIFT_STOP {
+        return Ok(None);
+    }
+    let delta = (byte >> 4) as i16;
+    *last_id = if delta == 0 {
+        let id = read_varint(reader)? as u16;
+        ((id >> 1) as i16) ^ -((id & 1) as i16)
+    } else {
+        last_id.wrapping_add(delta)
+    };
+    Ok(Some((field_type, *last_id)))
+}
+
+/// Skip a value of the given type.
+fn skip<R: Read>(reader: &mut R, value_type: u8, depth: usize) -> Result<(), DecodeError> {
+    if depth > THRIFT_MAX_DEPTH {
+        return Err(DecodeError::InvalidHeader("too deeply nested"));
+    }
+    match value_type {
+        THRIFT_BOOL_TRUE | THRIFT_BOOL_FALSE => {}
+        THRIFT_BYTE => {
+            read_byte(reader)?;
+        }
+        THRIFT_I16 | THRIFT_I32 | THRIFT_I64 => {
+            read_varint(reader)?;
+        }
+        THRIFT_DOUBLE => skip_bytes(reader, 8)?,
+        THRIFT_BINARY => {
+            let len = read_varint(reader)?;
+            skip_bytes(reader, len)?;
+        }
+        THRIFT_LIST | THRIFT_SET => {
+            let byte = read_byte(reader)?;
+            let size = match byte >> 4 {
+                15 => read_varint(reader)?,
+                size => size as u64,
+            };
+            // booleans are encoded as one byte inside collections
+            let element_type = match byte & 0x0f {
+                THRIFT_BOOL_TRUE | THRIFT_BOOL_FALSE => THRIFT_BYTE,
+                element_type => element_type,
+            };
+            for _ in 0..size {
+                skip(reader, element_type, depth + 1)?;
+            }
+        }
+        THRIFT_MAP => {
+            let size = read_varint(reader)?;
+            if size > 0 {
+                let types = read_byte(reader)?;
+                for _ in 0..size {
+                    skip(reader, types >> 4, depth + 1)?;
+                    skip(reader, types & 0x0f, depth + 1)?;
+                }
+            }
+        }
+        THRIFT_STRUCT => {
+            let mut last_id = 0;
+            while let Some((field_type, _)) = read_field_header(reader, &mut last_id)? {
+                skip(reader, field_type, depth + 1)?;
+            }
+        }
+        _ => return Err(DecodeError::InvalidHeader("unknown field type")),
+    }
+    Ok(())
+}
+
+fn read_byte<R: Read>(reader: &mut R) -> Result<u8, DecodeError> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> Result<u64, DecodeError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = read_byte(reader)?;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::InvalidHeader("varint is too long"))
+}
+
+fn skip_bytes<R: Read>(reader: &mut R, len: u64) -> Result<(), DecodeError> {
+    if io::copy(&mut reader.take(len), &mut io::sink())? != len {
+        return Err(DecodeError::Truncated);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_and_contains() {
+        let mut bloom = SplitBlockBloomFilter::with_ndv_and_fpp(10_000, 0.01).unwrap();
+        for i in 0..10_000i64 {
+            bloom.insert(&i);
+        }
+        assert!((0..10_000i64).all(|i| bloom.contains(&i)));
+
+        let false_positives = (10_000..210_000i64).filter(|i| bloom.contains(i)).count();
+        let measured = false_positives as f64 / 200_000.0;
+        assert!(measured < 0.01, "measured fp rate {measured}");
+    }
+
+    #[test]
+    fn sizing() {
+        // 10_000 values at 1% need 11_981 bytes, rounded up to a power of two
+        let bloom = SplitBlockBloomFilter::with_ndv_and_fpp(10_000, 0.01).unwrap();
+        assert_eq!(bloom.num_bytes(), 16_384);
+        let bloom = SplitBlockBloomFilter::with_ndv_and_fpp(1, 0.5).unwrap();
+        assert_eq!(bloom.num_bytes(), 32);
+        let bloom = SplitBlockBloomFilter::with_ndv_and_fpp(u64::MAX, 0.01).unwrap();
+        assert_eq!(bloom.num_bytes(), MAX_BYTES);
+
+        assert_eq!(
+            SplitBlockBloomFilter::new(48),
+            Err(BloomError::InvalidNumBytes(48))
+        );
+        assert_eq!(
+            SplitBlockBloomFilter::with_ndv_and_fpp(0, 0.01),
+            Err(BloomError::ZeroItemsCount)
+        );
+    }
+
+    #[test]
+    fn golden_hashes() {
+        // reference values of xxHash64 with seed 0
+        assert_eq!(xxh64(b"", 0), 0xef46_db37_51d8_e999);
+        assert_eq!(<[u8]>::sbbf_hash(b""), 0xef46_db37_51d8_e999);
+        assert_eq!("".sbbf_hash(), 0xef46_db37_51d8_e999);
+        assert_eq!(42i32.sbbf_hash(), xxh64(&[42, 0, 0, 0], 0));
+        assert_eq!(
+            1.0f64.sbbf_hash(),
+            xxh64(&0x3ff0_0000_0000_0000u64.to_le_bytes(), 0)
+        );
+    }
+
+    #[test]
+    fn golden_header() {
+        // header of a 32 bytes filter as written by parquet-mr, followed by the first byte of the bitset
+        let bytes = [21, 64, 28, 28, 0, 0, 28, 28, 0, 0, 28, 28, 0, 0, 0, 99];
+        assert_eq!(header(32), &bytes[..15]);
+
+        let mut reader = &bytes[..];
+        assert_eq!(read_header(&mut reader).unwrap(), 32);
+        assert_eq!(reader, &[99]);
+    }
+
+    #[test]
+    fn golden_bitset() {
+        // bitset written by parquet-mr for a BYTE_ARRAY column holding the strings "a0" to "a9"
+        let bitset: [u8; 32] = [
+            200, 1, 80, 20, 64, 68, 8, 109, 6, 37, 4, 67, 144, 80, 96, 32, 8, 132, 43, 33, 0, 5,
+            99, 65, 2, 0, 224, 44, 64, 78, 96, 4,
+        ];
+        let mut bytes = header(32);
+        bytes.extend_from_slice(&bitset);
+
+        let bloom = SplitBlockBloomFilter::from_bytes(&bytes).unwrap();
+        for i in 0..10 {
+            assert!(bloom.contains(format!("a{i}").as_str()));
+        }
+
+        let mut expected = SplitBlockBloomFilter::new(32).unwrap();
+        for i in 0..10 {
+            expected.insert(format!("a{i}").as_str());
+        }
+        assert_eq!(bloom, expected);
+        assert_eq!(expected.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut bloom = SplitBlockBloomFilter::new(1024).unwrap();
+        for i in 0..100u32 {
+            bloom.insert(&i);
+        }
+        let bytes = bloom.to_bytes();
+        assert_eq!(bytes.len(), 16 + 1024);
+        assert_eq!(SplitBlockBloomFilter::from_bytes(&bytes).unwrap(), bloom);
+    }
+
+    #[test]
+    fn header_with_unknown_fields() {
+        // long form field identifiers and unknown fields of various types are skipped
+        let bytes = [
+            0x05, 0x02, 0x40, // field 1 (long form), i32 32
+            0x28, 0x03, b'a', b'b', b'c', // field 3, binary "abc"
+            0x0c, 0x04, 0x1c, 0x00, 0x00, // field 2 (long form), union BLOCK
+            0x1c, 0x1c, 0x00, 0x00, // field 3, union XXHASH
+            0x1c, 0x1c, 0x00, 0x00, // field 4, union UNCOMPRESSED
+            0x39, 0x21, 0x01, 0x02, // field 7, list of two bytes
+            0x00,
+        ];
+        assert_eq!(read_header(&mut &bytes[..]).unwrap(), 32);
+    }
+
+    #[test]
+    fn rejects_invalid_payloads() {
+        let bytes = SplitBlockBloomFilter::new(32).unwrap().to_bytes();
+        assert!(matches!(
+            SplitBlockBloomFilter::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::Truncated)
+        ));
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(matches!(
+            SplitBlockBloomFilter::from_bytes(&trailing),
+            Err(DecodeError::TrailingBytes)
+        ));
+
+        let mut unsupported = bytes.clone();
+        // hash union holding its second member
+        unsupported[7] = 0x2c;
+        assert!(matches!(
+            SplitBlockBloomFilter::from_bytes(&unsupported),
+            Err(DecodeError::InvalidHeader("unsupported hash"))
+        ));
+
+        let mut invalid_size = bytes.clone();
+        invalid_size[1] = 0x30;
+        assert!(matches!(
+            SplitBlockBloomFilter::from_bytes(&invalid_size),
+            Err(DecodeError::InvalidParameters(BloomError::InvalidNumBytes(
+                24
+            )))
+        ));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{BloomError, BloomFilter};
+
+impl<T: ?Sized, S> BloomFilter<T, S> {
+    /// The number of bits set in the filter.
+    pub fn count_ones(&self) -> usize {
+        self.bitmap.count_ones()
+    }
+
+    /// The proportion of bits set in the filter, between 0 and 1.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.optimal_m as f64
+    }
+
+    /// Estimate the number of distinct items inserted in the filter from the number of bits set.
+    ///
+    /// This uses the estimator of Swamidass and Baldi, `n = -m / k * ln(1 - X / m)` with `X` the number of bits
+    /// set, which is accurate as long as the filter is not saturated. Returns infinity if all bits are set.
+    pub fn estimated_len(&self) -> f64 {
+        self.estimate_from_ones(self.count_ones())
+    }
+
+    /// The false positive rate of the filter given its actual fill, i.e. the probability that all the bits of
+    /// an item not in the filter are set.
+    pub fn current_fp_rate(&self) -> f64 {
+        self.fill_ratio().powi(self.optimal_k as i32)
+    }
+
+    fn estimate_from_ones(&self, ones: usize) -> f64 {
+        let m = self.optimal_m as f64;
+        -m / self.optimal_k as f64 * (-(ones as f64) / m).ln_1p()
+    }
+}
+
+impl<T: ?Sized, S: PartialEq> BloomFilter<T, S> {
+    /// Estimate the number of distinct items in the union of two filters, see [`BloomFilter::estimated_len`].
+    ///
+    /// Returns an error if the filters are not compatible, see [`BloomFilter::is_compatible`].
+    pub fn estimated_union_len(&self, other: &Self) -> Result<f64, BloomError> {
+        if !self.is_compatible(other) {
+            return Err(BloomError::IncompatibleFilters);
+        }
+        // compatible filters have the same number of bits and unused bits are always zero, so the union can
+        // be counted word by word without being allocated
+        let ones = self
+            .bitmap
+            .as_raw_slice()
+            .iter()
+            .zip(other.bitmap.as_raw_slice())
+            .map(|(a, b)| (a | b).count_ones() as usize)
+            .sum();
+        Ok(self.estimate_from_ones(ones))
+    }
+
+    /// Estimate the number of distinct items in the intersection of two filters, as the sum of the estimated
+    /// numbers of items of each filter minus the estimated number of items of their union.
+    ///
+    /// Returns an error if the filters are not compatible, see [`BloomFilter::is_compatible`].
+    pub fn estimated_intersection_len(&self, other: &Self) -> Result<f64, BloomError> {
+        let union = self.estimated_union_len(other)?;
+        Ok((self.estimated_len() + other.estimated_len() - union).max(0.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= expected * tolerance,
+            "{actual} is not within {tolerance} of {expected}"
+        );
+    }
+
+    #[test]
+    fn empty_filter() {
+        let bloom = BloomFilter::<u32>::with_seed(10_000, 0.01, 0);
+        assert_eq!(bloom.count_ones(), 0);
+        assert_eq!(bloom.fill_ratio(), 0.0);
+        assert_eq!(bloom.estimated_len(), 0.0);
+        assert_eq!(bloom.current_fp_rate(), 0.0);
+    }
+
+    #[test]
+    fn estimates_follow_inserts() {
+        let mut bloom = BloomFilter::with_seed(10_000, 0.01, 0);
+        for i in 0..10_000u32 {
+            bloom.insert(&i);
+            // repeated inserts don't change the estimate
+            bloom.insert(&i);
+            if (i + 1) % 2500 == 0 {
+                assert_close(bloom.estimated_len(), (i + 1) as f64, 0.03);
+            }
+        }
+        // the expected fill ratio is 1 - e^(-k * n / m), close to half full for an optimally sized filter
+        let expected_fill =
+            -(-(bloom.num_hashes() as f64) * 10_000.0 / bloom.num_bits() as f64).exp_m1();
+        assert_close(bloom.fill_ratio(), expected_fill, 0.01);
+        assert_close(bloom.current_fp_rate(), 0.01, 0.15);
+        assert_eq!(
+            bloom.count_ones(),
+            (bloom.fill_ratio() * bloom.num_bits() as f64).round() as usize
+        );
+    }
+
+    #[test]
+    fn union_and_intersection_estimates() {
+        let mut bloom_1 = BloomFilter::with_seed(10_000, 0.01, 0);
+        let mut bloom_2 = BloomFilter::with_seed(10_000, 0.01, 0);
+        for i in 0..6000u32 {
+            bloom_1.insert(&i);
+            bloom_2.insert(&(i + 4000));
+        }
+        assert_close(
+            bloom_1.estimated_union_len(&bloom_2).unwrap(),
+            10_000.0,
+            0.03,
+        );
+        assert_close(
+            bloom_1.estimated_intersection_len(&bloom_2).unwrap(),
+            2000.0,
+            0.1,
+        );
+        assert_eq!(
+            bloom_1.estimated_union_len(&bloom_2).unwrap(),
+            (&bloom_1 | &bloom_2).estimated_len()
+        );
+
+        let other = BloomFilter::with_seed(10_000, 0.01, 1);
+        assert_eq!(
+            bloom_1.estimated_union_len(&other),
+            Err(BloomError::IncompatibleFilters)
+        );
+        assert_eq!(
+            bloom_1.estimated_intersection_len(&other),
+            Err(BloomError::IncompatibleFilters)
+        );
+    }
+
+    #[test]
+    fn saturated_filter() {
+        let mut bloom = BloomFilter::with_bits_and_hashes(64, 1);
+        for i in 0..10_000u32 {
+            bloom.insert(&i);
+        }
+        assert_eq!(bloom.fill_ratio(), 1.0);
+        assert_eq!(bloom.current_fp_rate(), 1.0);
+        assert_eq!(bloom.estimated_len(), f64::INFINITY);
+    }
+}
@@ -0,0 +1,573 @@
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+    io::{self, Read, Write},
+    marker::PhantomData,
+};
+
+use crate::{
+    hash,
+    serialization::{Crc32Reader, Crc32Writer, CHUNK_WORDS, HASH_SIPHASH_1_3, VERSION},
+    BloomError, DecodeError, SeededState,
+};
+
+/// Magic bytes opening every serialized xor filter.
+const MAGIC: [u8; 4] = *b"XORF";
+/// Size of the header of serialized static filters: magic, version, hash algorithm, fingerprint bits, seed,
+/// construction seed, number of keys, segment length and number of fingerprints.
+const HEADER_LEN: usize = 4 + 1 + 1 + 1 + 8 + 8 + 8 + 4 + 8;
+/// Maximum number of seeds tried to build a static filter before giving up.
+const MAX_ATTEMPTS: usize = 100;
+
+mod private {
+    use std::ops::BitXor;
+
+    pub trait Sealed: Copy + Default + Eq + BitXor<Output = Self> {
+        const BITS: u32;
+
+        fn from_hash(hash: u64) -> Self;
+
+        fn extend_le(self, bytes: &mut Vec<u8>);
+
+        fn from_le(bytes: &[u8]) -> Self;
+    }
+
+    impl Sealed for u8 {
+        const BITS: u32 = 8;
+
+        fn from_hash(hash: u64) -> Self {
+            (hash ^ (hash >> 32)) as u8
+        }
+
+        fn extend_le(self, bytes: &mut Vec<u8>) {
+            bytes.push(self);
+        }
+
+        fn from_le(bytes: &[u8]) -> Self {
+            bytes[0]
+        }
+    }
+
+    impl Sealed for u16 {
+        const BITS: u32 = 16;
+
+        fn from_hash(hash: u64) -> Self {
+            (hash ^ (hash >> 32)) as u16
+        }
+
+        fn extend_le(self, bytes: &mut Vec<u8>) {
+            bytes.extend_from_slice(&self.to_le_bytes());
+        }
+
+        fn from_le(bytes: &[u8]) -> Self {
+            u16::from_le_bytes([bytes[0], bytes[1]])
+        }
+    }
+}
+
+/// The fingerprints stored by static filters: `u8` for a false positive rate of about 0.4%, or `u16` for about
+/// 0.0015%.
+pub trait Fingerprint: private::Sealed {}
+
+impl Fingerprint for u8 {}
+
+impl Fingerprint for u16 {}
+
+/// An xor filter, a static membership structure built once from a set of keys.
+///
+/// Each key is mapped to three slots, one in each third of an array of `1.23 * n` fingerprints, and the array is
+/// filled so that the fingerprint of every key is the xor of its three slots. Lookups read three slots and never
+/// report a false negative, with a false positive rate of `1 / 2^bits` for fingerprints of `bits` bits: about 0.4%
+/// for [`XorFilter8`] with 9.84 bits per key, where a [`BloomFilter`](crate::BloomFilter) needs 11.5 bits per key.
+/// Keys can't be added once the filter is built.
+///
+/// Keys are hashed by the same hash builders as bloom filters, then mixed with a construction seed which is
+/// changed until the array can be filled, which almost always works on the first attempt.
+///
+/// Example usage:
+/// ```
+/// use bloom_filter::XorFilter8;
+///
+/// let keys: Vec<u64> = (0..1000).collect();
+/// let xor = XorFilter8::<u64>::from_keys(&keys);
+/// assert!(keys.iter().all(|key| xor.contains(key)));
+/// ```
+pub struct XorFilter<T: ?Sized, F = u8, S = SeededState> {
+    fingerprints: Vec<F>,
+    block_length: usize,
+    seed: u64,
+    len: usize,
+    hash_builder: S,
+    _marker: PhantomData<fn(&T)>,
+}
+
+/// An [`XorFilter`] with 8-bit fingerprints, for a false positive rate of about 0.4%.
+pub type XorFilter8<T, S = SeededState> = XorFilter<T, u8, S>;
+
+/// An [`XorFilter`] with 16-bit fingerprints, for a false positive rate of about 0.0015%.
+pub type XorFilter16<T, S = SeededState> = XorFilter<T, u16, S>;
+
+impl<T: ?Sized + Hash, F: Fingerprint> XorFilter<T, F> {
+    /// Build an XorFilter holding `keys`, hashing them with a random seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the filter can't be built, see [`XorFilter::try_with_hasher`].
+    pub fn from_keys<'a, I>(keys: I) -> Self
+    where
+        T: 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        Self::with_hasher(keys, SeededState::new())
+    }
+
+    /// Build an XorFilter holding `keys`, hashing them deterministically from `seed`, see
+    /// [`BloomFilter::with_seed`](crate::BloomFilter::with_seed).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the filter can't be built, see [`XorFilter::try_with_hasher`].
+    pub fn with_seed<'a, I>(keys: I, seed: u64) -> Self
+    where
+        T: 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        Self::with_hasher(keys, SeededState::with_seed(seed))
+    }
+}
+
+impl<T: ?Sized + Hash, F: Fingerprint, S: BuildHasher> XorFilter<T, F, S> {
+    /// Build an XorFilter holding `keys`, hashing them with hashers built by `hash_builder`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the filter can't be built, see [`XorFilter::try_with_hasher`].
+    pub fn with_hasher<'a, I>(keys: I, hash_builder: S) -> Self
+    where
+        T: 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        match Self::try_with_hasher(keys, hash_builder) {
+            Ok(xor) => xor,
+            Err(err) => panic!("failed to build xor filter: {err}"),
+        }
+    }
+
+    /// Fallible version of [`XorFilter::with_hasher`].
+    ///
+    /// Duplicate keys are ignored. Returns [`BloomError::ConstructionFailed`] if the keys failed to peel with each
+    /// of the 100 construction seeds, which is astronomically unlikely as each seed almost always works, or
+    /// [`BloomError::BitmapSizeOverflow`] if there are more than `2^32` keys.
+    pub fn try_with_hasher<'a, I>(keys: I, hash_builder: S) -> Result<Self, BloomError>
+    where
+        T: 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        let key_hashes = unique_hashes(&hash_builder, keys)?;
+        let block_length = (key_hashes.len() * 123 / 100 + 32).div_ceil(3);
+        let (seed, fingerprints) = build(&key_hashes, 3 * block_length, |hash| {
+            positions(hash, block_length)
+        })?;
+
+        Ok(XorFilter {
+            fingerprints,
+            block_length,
+            seed,
+            len: key_hashes.len(),
+            hash_builder,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Checks if an element is contained in the filter, see [`BloomFilter::contains`](crate::BloomFilter::contains).
+    pub fn contains<Q: ?Sized + Hash>(&self, item: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        let hash = hash::mix(self.hash_builder.hash_one(item), self.seed);
+        let [h0, h1, h2] = positions(hash, self.block_length);
+        F::from_hash(hash) == self.fingerprints[h0] ^ self.fingerprints[h1] ^ self.fingerprints[h2]
+    }
+}
+
+impl<T: ?Sized, F: Fingerprint, S> XorFilter<T, F, S> {
+    /// The number of distinct keys the filter was built from.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the filter was built from no key.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of bits of this filter.
+    pub fn num_bits(&self) -> usize {
+        self.fingerprints.len() * F::BITS as usize
+    }
+
+    /// The hash builder used by this filter.
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+}
+
+impl<T: ?Sized, F: Fingerprint> XorFilter<T, F, SeededState> {
+    /// Serialize the filter into a versioned binary payload.
+    ///
+    /// The payload is made of, with all integers in little endian:
+    /// - the magic bytes `XORF`, or `BFUF` for a [`BinaryFuseFilter`](crate::BinaryFuseFilter),
+    /// - the format version as a `u8`, currently 1,
+    /// - the hash algorithm identifier as a `u8`, 1 for the SipHash-1-3 scheme of [`SeededState`],
+    /// - the number of bits of the fingerprints as a `u8`,
+    /// - the seed as a `u64`,
+    /// - the construction seed as a `u64`,
+    /// - the number of keys as a `u64`,
+    /// - the segment length as a `u32`, which is the length of each third of the fingerprints for an xor filter,
+    /// - the number of fingerprints as a `u64`,
+    /// - the fingerprints,
+    /// - the CRC32 (IEEE) of all the previous bytes as a `u32`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(HEADER_LEN + self.fingerprints.len() * F::BITS as usize / 8 + 4);
+        self.write_to(&mut bytes)
+            .expect("writing to a Vec never fails");
+        bytes
+    }
+
+    /// Deserialize a filter from a payload produced by [`XorFilter::to_bytes`].
+    ///
+    /// Returns an error if the payload is corrupt, truncated, followed by extra bytes, was produced by an
+    /// incompatible version of the format or holds fingerprints of another size.
+    pub fn from_bytes(mut bytes: &[u8]) -> Result<Self, DecodeError> {
+        let xor = Self::read_from(&mut bytes)?;
+        if !bytes.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+        Ok(xor)
+    }
+
+    /// Serialize the filter into `writer`, see [`XorFilter::to_bytes`] for the format.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        let parts = StaticFilterParts {
+            hash_seed: self.hash_builder.seed(),
+            seed: self.seed,
+            len: self.len as u64,
+            segment_length: self.block_length as u32,
+            fingerprints: &self.fingerprints[..],
+        };
+        parts.write_to(writer, MAGIC)
+    }
+
+    /// Deserialize a filter from `reader`, see [`XorFilter::from_bytes`].
+    ///
+    /// The reader is left positioned right after the filter.
+    pub fn read_from<R: Read>(reader: R) -> Result<Self, DecodeError> {
+        let parts = StaticFilterParts::<Vec<F>>::read_from(reader, MAGIC)?;
+        let block_length = parts.segment_length as usize;
+        let expected = 3 * block_length;
+        if block_length == 0 || parts.fingerprints.len() != expected {
+            return Err(DecodeError::InvalidBitmapLength {
+                expected,
+                actual: parts.fingerprints.len(),
+            });
+        }
+
+        Ok(XorFilter {
+            fingerprints: parts.fingerprints,
+            block_length,
+            seed: parts.seed,
+            len: usize::try_from(parts.len).unwrap_or(usize::MAX),
+            hash_builder: SeededState::with_seed(parts.hash_seed),
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// The slots of a key in each third of the fingerprints of an xor filter, given its mixed hash.
+fn positions(hash: u64, block_length: usize) -> [usize; 3] {
+    let reduce = |hash: u64| ((hash as u32 as u64 * block_length as u64) >> 32) as usize;
+    [
+        reduce(hash),
+        block_length + reduce(hash.rotate_left(21)),
+        2 * block_length + reduce(hash.rotate_left(42)),
+    ]
+}
+
+/// Hash every key, without duplicates.
+pub(crate) fn unique_hashes<'a, T, S, I>(hash_builder: &S, keys: I) -> Result<Vec<u64>, BloomError>
+where
+    T: ?Sized + Hash + 'a,
+    S: BuildHasher,
+    I: IntoIterator<Item = &'a T>,
+{
+    let mut key_hashes: Vec<u64> = keys
+        .into_iter()
+        .map(|key| hash_builder.hash_one(key))
+        .collect();
+    key_hashes.sort_unstable();
+    key_hashes.dedup();
+    if key_hashes.len() > u32::MAX as usize {
+        return Err(BloomError::BitmapSizeOverflow);
+    }
+    Ok(key_hashes)
+}
+
+/// The construction seeds tried in turn to build a static filter.
+///
+/// They come from SplitMix64 started at 0, so that the construction only depends on the keys.
+pub(crate) fn construction_seeds() -> impl Iterator<Item = u64> {
+    let mut rng = 0u64;
+    (0..MAX_ATTEMPTS).map(move |_| {
+        rng = rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut seed = rng;
+        seed = (seed ^ (seed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        seed = (seed ^ (seed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        seed ^ (seed >> 31)
+    })
+}
+
+/// Fill an array of `len` fingerprints such that the fingerprint of every key is the xor of its three slots given
+/// by `positions`, trying new construction seeds until it works. Returns the seed along with the fingerprints.
+pub(crate) fn build<F: Fingerprint>(
+    key_hashes: &[u64],
+    len: usize,
+    positions: impl Fn(u64) -> [usize; 3],
+) -> Result<(u64, Vec<F>), BloomError> {
+    let mut counts = vec![0u32; len];
+    let mut xors = vec![0u64; len];
+    let mut queue = Vec::new();
+    let mut stack = Vec::with_capacity(key_hashes.len());
+
+    for seed in construction_seeds() {
+        counts.fill(0);
+        xors.fill(0);
+        for &key_hash in key_hashes {
+            let hash = hash::mix(key_hash, seed);
+            for position in positions(hash) {
+                counts[position] += 1;
+                xors[position] ^= hash;
+            }
+        }
+
+        // peel the keys: a slot used by a single key can be set last to fix the xor of this key, so remove this
+        // key from its other slots, which may leave them with a single key in turn
+        queue.clear();
+        queue.extend((0..len).filter(|&position| counts[position] == 1));
+        stack.clear();
+        while let Some(position) = queue.pop() {
+            if counts[position] != 1 {
+                continue;
+            }
+            let hash = xors[position];
+            stack.push((hash, position));
+            for other in positions(hash) {
+                counts[other] -= 1;
+                xors[other] ^= hash;
+                if counts[other] == 1 {
+                    queue.push(other);
+                }
+            }
+        }
+        if stack.len() < key_hashes.len() {
+            continue;
+        }
+
+        // assign the slots in the reverse order of the peeling, each slot being the last one of its key to be set
+        let mut fingerprints = vec![F::default(); len];
+        for &(hash, position) in stack.iter().rev() {
+            let [h0, h1, h2] = positions(hash);
+            fingerprints[position] =
+                F::from_hash(hash) ^ fingerprints[h0] ^ fingerprints[h1] ^ fingerprints[h2];
+        }
+        return Ok((seed, fingerprints));
+    }
+
+    Err(BloomError::ConstructionFailed)
+}
+
+/// The serialized fields of a static filter.
+pub(crate) struct StaticFilterParts<V> {
+    pub(crate) hash_seed: u64,
+    pub(crate) seed: u64,
+    pub(crate) len: u64,
+    pub(crate) segment_length: u32,
+    pub(crate) fingerprints: V,
+}
+
+impl<F: Fingerprint> StaticFilterParts<&[F]> {
+    pub(crate) fn write_to<W: Write>(&self, writer: W, magic: [u8; 4]) -> io::Result<()> {
+        let mut writer = Crc32Writer {
+            inner: writer,
+            hasher: crc32fast::Hasher::new(),
+        };
+
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&magic);
+        header.push(VERSION);
+        header.push(HASH_SIPHASH_1_3);
+        header.push(F::BITS as u8);
+        header.extend_from_slice(&self.hash_seed.to_le_bytes());
+        header.extend_from_slice(&self.seed.to_le_bytes());
+        header.extend_from_slice(&self.len.to_le_bytes());
+        header.extend_from_slice(&self.segment_length.to_le_bytes());
+        header.extend_from_slice(&(self.fingerprints.len() as u64).to_le_bytes());
+        writer.write_all(&header)?;
+
+        let mut buffer = Vec::with_capacity(8 * CHUNK_WORDS);
+        for chunk in self
+            .fingerprints
+            .chunks(8 * CHUNK_WORDS / (F::BITS as usize / 8))
+        {
+            buffer.clear();
+            for fingerprint in chunk {
+                fingerprint.extend_le(&mut buffer);
+            }
+            writer.write_all(&buffer)?;
+        }
+
+        let checksum = writer.hasher.finalize();
+        writer.inner.write_all(&checksum.to_le_bytes())
+    }
+}
+
+impl<F: Fingerprint> StaticFilterParts<Vec<F>> {
+    pub(crate) fn read_from<R: Read>(reader: R, magic: [u8; 4]) -> Result<Self, DecodeError> {
+        let mut reader = Crc32Reader {
+            inner: reader,
+            hasher: crc32fast::Hasher::new(),
+        };
+
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        let (header_magic, rest) = header.split_at(4);
+        if header_magic != magic {
+            return Err(DecodeError::InvalidMagic);
+        }
+        if rest[0] != VERSION {
+            return Err(DecodeError::UnsupportedVersion(rest[0]));
+        }
+        if rest[1] != HASH_SIPHASH_1_3 {
+            return Err(DecodeError::UnsupportedHashAlgorithm(rest[1]));
+        }
+        if rest[2] as u32 != F::BITS {
+            return Err(DecodeError::InvalidHeader("unexpected fingerprint size"));
+        }
+        let hash_seed = u64::from_le_bytes(rest[3..11].try_into().unwrap());
+        let seed = u64::from_le_bytes(rest[11..19].try_into().unwrap());
+        let len = u64::from_le_bytes(rest[19..27].try_into().unwrap());
+        let segment_length = u32::from_le_bytes(rest[27..31].try_into().unwrap());
+        let count = usize::try_from(u64::from_le_bytes(rest[31..39].try_into().unwrap()))
+            .map_err(|_| DecodeError::InvalidParameters(BloomError::BitmapSizeOverflow))?;
+
+        // don't trust the header for the allocation, a corrupt payload would otherwise abort the process
+        let bytes_per_fingerprint = F::BITS as usize / 8;
+        let chunk_len = 8 * CHUNK_WORDS / bytes_per_fingerprint;
+        let mut fingerprints = Vec::with_capacity(count.min(chunk_len));
+        let mut buffer = vec![0; 8 * CHUNK_WORDS];
+        while fingerprints.len() < count {
+            let chunk =
+                &mut buffer[..bytes_per_fingerprint * (count - fingerprints.len()).min(chunk_len)];
+            reader.read_exact(chunk)?;
+            fingerprints.extend(chunk.chunks_exact(bytes_per_fingerprint).map(F::from_le));
+        }
+
+        let computed = reader.hasher.finalize();
+        let mut checksum = [0; 4];
+        reader.inner.read_exact(&mut checksum)?;
+        let expected = u32::from_le_bytes(checksum);
+        if expected != computed {
+            return Err(DecodeError::ChecksumMismatch { expected, computed });
+        }
+
+        Ok(StaticFilterParts {
+            hash_seed,
+            seed,
+            len,
+            segment_length,
+            fingerprints,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contains_all_keys() {
+        let keys: Vec<u64> = (0..10_000).collect();
+        let xor = XorFilter8::<u64>::with_seed(&keys, 0);
+        assert_eq!(xor.len(), 10_000);
+        assert!(keys.iter().all(|key| xor.contains(key)));
+
+        let xor = XorFilter16::<u64>::with_seed(&keys, 0);
+        assert!(keys.iter().all(|key| xor.contains(key)));
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let keys: Vec<u64> = (0..100_000).collect();
+        let measure = |contains: &dyn Fn(&u64) -> bool| {
+            (100_000..1_100_000u64).filter(|i| contains(i)).count() as f64 / 1_000_000.0
+        };
+
+        let xor = XorFilter8::<u64>::with_seed(&keys, 0);
+        let fp_rate = measure(&|key| xor.contains(key));
+        assert!((fp_rate - 1.0 / 256.0).abs() < 0.0005, "{fp_rate}");
+        let bits_per_key = xor.num_bits() as f64 / xor.len() as f64;
+        assert!(bits_per_key < 9.9, "{bits_per_key}");
+
+        let xor = XorFilter16::<u64>::with_seed(&keys, 0);
+        let fp_rate = measure(&|key| xor.contains(key));
+        assert!(fp_rate < 0.0001, "{fp_rate}");
+    }
+
+    #[test]
+    fn duplicates_and_empty_keys() {
+        let keys = ["a", "b", "a", "c", "b"];
+        let xor = XorFilter8::<str>::with_seed(keys, 0);
+        assert_eq!(xor.len(), 3);
+        assert!(keys.iter().all(|key| xor.contains(key)));
+
+        let xor = XorFilter8::<str>::with_seed([], 0);
+        assert!(xor.is_empty());
+        assert!(!xor.contains("a"));
+    }
+
+    #[test]
+    fn same_seed_same_filter() {
+        let keys: Vec<u32> = (0..1000).collect();
+        let xor_1 = XorFilter8::<u32>::with_seed(&keys, 7);
+        let xor_2 = XorFilter8::<u32>::with_seed(keys.iter().rev(), 7);
+        assert_eq!(xor_1.fingerprints, xor_2.fingerprints);
+    }
+
+    #[test]
+    fn round_trip() {
+        let keys: Vec<u64> = (0..10_000).collect();
+        let xor = XorFilter16::<u64>::with_seed(&keys, 3);
+        let bytes = xor.to_bytes();
+        assert_eq!(bytes.len(), HEADER_LEN + 2 * xor.fingerprints.len() + 4);
+
+        let decoded = XorFilter16::<u64>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.fingerprints, xor.fingerprints);
+        assert_eq!(decoded.len(), 10_000);
+        assert!(keys.iter().all(|key| decoded.contains(key)));
+
+        assert!(matches!(
+            XorFilter8::<u64>::from_bytes(&bytes),
+            Err(DecodeError::InvalidHeader(_))
+        ));
+        let mut corrupt = bytes.clone();
+        corrupt[HEADER_LEN] ^= 1;
+        assert!(matches!(
+            XorFilter16::<u64>::from_bytes(&corrupt),
+            Err(DecodeError::ChecksumMismatch { .. })
+        ));
+        assert!(matches!(
+            XorFilter16::<u64>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::Truncated)
+        ));
+    }
+}